@@ -1,4 +1,4 @@
-use std::convert::{From, Into};
+use std::convert::From;
 use std::{env, fmt, path::PathBuf};
 
 #[derive(Debug, Clone)]
@@ -6,12 +6,15 @@ enum Setting {
     Version,
     Help,
     Untrusted,
+    MaxDepth(usize),
     Unknown(String),
 }
 
 const VERSION: &str = "--الإصدار";
 const HELP: &str = "--ساعد";
 const UNTRUSTED: &str = "--غير-موثوق";
+/// Followed by `=` and the maximum number of nested calls.
+const MAX_DEPTH: &str = "--أقصى-عمق";
 
 impl From<String> for Setting {
     fn from(value: String) -> Self {
@@ -19,18 +22,26 @@ impl From<String> for Setting {
             VERSION => Self::Version,
             HELP => Self::Help,
             UNTRUSTED => Self::Untrusted,
-            string => Self::Unknown(string.to_owned()),
+            string => match string
+                .strip_prefix(MAX_DEPTH)
+                .and_then(|rest| rest.strip_prefix('='))
+                .and_then(|depth| depth.parse().ok())
+            {
+                Some(depth) => Self::MaxDepth(depth),
+                None => Self::Unknown(string.to_owned()),
+            },
         }
     }
 }
 
-impl Into<String> for Setting {
-    fn into(self) -> String {
-        match self {
-            Self::Version => VERSION.to_owned(),
-            Self::Help => HELP.to_owned(),
-            Self::Untrusted => UNTRUSTED.to_owned(),
-            Self::Unknown(string) => string,
+impl From<Setting> for String {
+    fn from(setting: Setting) -> Self {
+        match setting {
+            Setting::Version => VERSION.to_owned(),
+            Setting::Help => HELP.to_owned(),
+            Setting::Untrusted => UNTRUSTED.to_owned(),
+            Setting::MaxDepth(depth) => format!("{MAX_DEPTH}={depth}"),
+            Setting::Unknown(string) => string,
         }
     }
 }
@@ -44,7 +55,7 @@ enum Token {
 fn lex(iter: &mut env::Args) -> Result<Vec<Token>, ParseError> {
     iter.next();
     let mut tokens = vec![];
-    while let Some(string) = iter.next() {
+    for string in iter {
         match string.as_str() {
             x if x.starts_with("--") => tokens.push(Token::Setting(Setting::from(string))),
             path => tokens.push(Token::Path(PathBuf::from(path))),
@@ -87,11 +98,8 @@ fn parse(tokens: Vec<Token>) -> Result<Args, ParseError> {
     let mut iter = tokens.iter().peekable();
     let mut settings = vec![];
     while let Some(Token::Setting(setting)) = iter.peek() {
-        match setting {
-            Setting::Unknown(string) => {
-                return Err(ParseError::ExpectedPathOrSetting(string.clone()))
-            }
-            _ => {}
+        if let Setting::Unknown(string) = setting {
+            return Err(ParseError::ExpectedPathOrSetting(string.clone()));
         }
         settings.push(setting.to_owned());
         iter.next();
@@ -112,7 +120,8 @@ pub enum EvalMode {
 
 #[derive(Clone)]
 pub enum Action {
-    Eval(EvalMode),
+    /// The mode, and the maximum number of nested calls.
+    Eval(EvalMode, usize),
     Version,
     Help,
 }
@@ -135,6 +144,7 @@ impl TryFrom<Args> for Action {
     fn try_from(value: Args) -> Result<Self, Self::Error> {
         let mut expect_path = false;
         let mut untrusted = false;
+        let mut max_depth = vm::MAX_DEPTH;
         for setting in value.settings {
             match setting {
                 Setting::Help => return Ok(Self::Help),
@@ -143,16 +153,17 @@ impl TryFrom<Args> for Action {
                     expect_path = true;
                     untrusted = true;
                 }
+                Setting::MaxDepth(depth) => max_depth = depth,
                 _ => unreachable!(),
             }
         }
         match value.path {
-            Some(path) => Ok(Self::Eval(EvalMode::File(path, untrusted))),
+            Some(path) => Ok(Self::Eval(EvalMode::File(path, untrusted), max_depth)),
             None => {
                 if expect_path {
                     Err(CompileError::ExpectedPath)
                 } else {
-                    Ok(Self::Eval(EvalMode::Repl))
+                    Ok(Self::Eval(EvalMode::Repl, max_depth))
                 }
             }
         }
@@ -190,4 +201,4 @@ pub fn get_action() -> Result<Action, Error> {
     let tokens = lex(&mut env::args())?;
     let args = parse(tokens)?;
     Ok(Action::try_from(args)?)
-}
\ No newline at end of file
+}
@@ -16,6 +16,8 @@ const HELP_MSG: &str = "
 الإعدادات:
   --غير-موثوق
     يمنع المستخدم من استخدام الخواص الخطيرة مثل قراءة الملفات وتغيير محتواها (لاحظ: يجب عليكم توفير الملف).
+  --أقصى-عمق=<العدد>
+    يحدد أقصى عدد من الاستدعاءات المتداخلة، وهو 1000 إن لم يحدد.
   --الإصدار
     يقوم بطباعة الإصدار المستخدم حالياً (لاحظ: هذا الأمر يتجاهل الملف).
   --ساعد
@@ -33,8 +35,10 @@ fn main() {
 
 fn try_main() -> Result<(), Error> {
     match get_action()? {
-        Action::Eval(EvalMode::File(path, untrusted)) => file(path, untrusted)?,
-        Action::Eval(EvalMode::Repl) => repl()?,
+        Action::Eval(EvalMode::File(path, untrusted), max_depth) => {
+            file(path, untrusted, max_depth)?
+        }
+        Action::Eval(EvalMode::Repl, max_depth) => repl(max_depth)?,
         Action::Version => println!("{}", env!("CARGO_PKG_VERSION")),
         Action::Help => {
             println!(
@@ -102,7 +106,7 @@ impl fmt::Display for Error {
             Self::Parser(errors) => {
                 let mut iter = errors.iter();
                 write!(f, "{}", iter.next().unwrap())?;
-                while let Some(error) = iter.next() {
+                for error in iter {
                     write!(f, "\n{error}")?;
                 }
                 Ok(())
@@ -110,7 +114,7 @@ impl fmt::Display for Error {
             Self::Compile(errors) => {
                 let mut iter = errors.iter();
                 write!(f, "{}", iter.next().unwrap())?;
-                while let Some(error) = iter.next() {
+                for error in iter {
                     write!(f, "\n{error}")?;
                 }
                 Ok(())
@@ -128,9 +132,9 @@ impl fmt::Display for Error {
     }
 }
 
-fn repl() -> Result<(), ReadlineError> {
+fn repl(max_depth: usize) -> Result<(), ReadlineError> {
     let mut rl = Editor::<()>::new()?;
-    let mut vm = Vm::new();
+    let mut vm = Vm::with_max_depth(max_depth);
     loop {
         let readline = rl.readline(">>> ");
         match readline {
@@ -157,9 +161,9 @@ fn repl() -> Result<(), ReadlineError> {
     Ok(())
 }
 
-fn file(path: PathBuf, untrusted: bool) -> Result<(), Error> {
+fn file(path: PathBuf, untrusted: bool, max_depth: usize) -> Result<(), Error> {
     let source = fs::read_to_string(&path)?;
-    let mut vm = Vm::new();
+    let mut vm = Vm::with_max_depth(max_depth);
     run(&mut vm, source, Some(path), untrusted)
 }
 
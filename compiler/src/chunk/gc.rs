@@ -0,0 +1,349 @@
+//! Frees the objects that reference each other in cycles, which reference counting alone never does.
+//!
+//! The objects that can be mutated to form cycles are tracked when they're created. A collection finds every object that
+//! is reachable from the tracked ones, and subtracts the references the found objects hold to each other from their
+//! reference counts. The objects with references left are referenced from outside (e.g., by the VM), so they and what
+//! they reach are alive. The rest are only referenced by each other, so their contents are cleared to break the cycles.
+
+use super::value::{
+    BoundMethod, Class, Closure, Generator, Instance, Iterable, Iterator, Module, Object, Upvalue,
+    Value,
+};
+use crate::error::RuntimeError;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/// The number of objects that have to be created before the first collection.
+const MIN_THRESHOLD: usize = 1000;
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    /// The objects that are being tracked, some of which may have been freed since the last collection.
+    pub tracked: usize,
+    pub collections: usize,
+    /// The objects freed by all of the collections.
+    pub freed: usize,
+}
+
+struct Heap {
+    tracked: Vec<Tracked>,
+    /// The objects created since the last collection.
+    allocated: usize,
+    /// How many objects have to be created for the next collection to happen, which grows with the surviving objects so
+    /// that collections take linear time overall.
+    threshold: usize,
+    collections: usize,
+    freed: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            tracked: vec![],
+            allocated: 0,
+            threshold: MIN_THRESHOLD,
+            collections: 0,
+            freed: 0,
+        }
+    }
+}
+
+/// The objects that cycles have to pass through.
+pub(super) enum Tracked {
+    List(Weak<RefCell<Vec<Value>>>),
+    HashMap(Weak<RefCell<HashMap<String, Value>>>),
+    Instance(Weak<RefCell<Instance>>),
+    Generator(Weak<RefCell<Generator>>),
+    Upvalue(Weak<RefCell<Upvalue>>),
+}
+
+impl Tracked {
+    fn is_alive(&self) -> bool {
+        match self {
+            Self::List(list) => list.strong_count() > 0,
+            Self::HashMap(hash_map) => hash_map.strong_count() > 0,
+            Self::Instance(instance) => instance.strong_count() > 0,
+            Self::Generator(generator) => generator.strong_count() > 0,
+            Self::Upvalue(upvalue) => upvalue.strong_count() > 0,
+        }
+    }
+
+    fn upgrade(&self) -> Option<Node> {
+        match self {
+            Self::List(list) => list.upgrade().map(Node::List),
+            Self::HashMap(hash_map) => hash_map.upgrade().map(Node::HashMap),
+            Self::Instance(instance) => instance.upgrade().map(Node::Instance),
+            Self::Generator(generator) => generator.upgrade().map(Node::Generator),
+            Self::Upvalue(upvalue) => upvalue.upgrade().map(Node::Upvalue),
+        }
+    }
+}
+
+pub(super) fn track(tracked: Tracked) {
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.tracked.push(tracked);
+        heap.allocated += 1;
+    })
+}
+
+/// Whether enough objects were created since the last collection for another one to happen.
+pub fn should_collect() -> bool {
+    HEAP.with(|heap| {
+        let heap = heap.borrow();
+        heap.allocated >= heap.threshold
+    })
+}
+
+pub fn stats() -> Stats {
+    HEAP.with(|heap| {
+        let heap = heap.borrow();
+        Stats {
+            tracked: heap.tracked.len(),
+            collections: heap.collections,
+            freed: heap.freed,
+        }
+    })
+}
+
+/// Frees the objects that are only referenced by cycles, and returns their count.
+///
+/// None of the objects can be borrowed while collecting.
+pub fn collect() -> usize {
+    // Every object is tracked once, when it's created.
+    let mut nodes = HEAP.with(|heap| {
+        heap.borrow()
+            .tracked
+            .iter()
+            .filter_map(Tracked::upgrade)
+            .collect::<Vec<_>>()
+    });
+    let mut ids = nodes
+        .iter()
+        .enumerate()
+        .map(|(idx, node)| (node.id(), idx))
+        .collect::<HashMap<_, _>>();
+    // Each found object is held once by `nodes`, and its edges are kept as indices so that no other references are left
+    // when counting.
+    let mut edges = vec![];
+    let mut idx = 0;
+    while idx < nodes.len() {
+        let mut node_edges = vec![];
+        for child in nodes[idx].children() {
+            let child_idx = *ids.entry(child.id()).or_insert_with(|| {
+                nodes.push(child);
+                nodes.len() - 1
+            });
+            node_edges.push(child_idx);
+        }
+        edges.push(node_edges);
+        idx += 1;
+    }
+
+    let mut refs = nodes
+        .iter()
+        .map(|node| node.strong_count() - 1)
+        .collect::<Vec<_>>();
+    for node_edges in &edges {
+        for child_idx in node_edges {
+            refs[*child_idx] -= 1;
+        }
+    }
+    let mut alive = vec![false; nodes.len()];
+    let mut stack = (0..nodes.len())
+        .filter(|idx| refs[*idx] > 0)
+        .collect::<Vec<_>>();
+    while let Some(idx) = stack.pop() {
+        if !alive[idx] {
+            alive[idx] = true;
+            stack.extend(edges[idx].iter().filter(|child_idx| !alive[**child_idx]));
+        }
+    }
+
+    // The contents are dropped after all of the objects are cleared, since dropping them may free other garbage.
+    let mut contents = vec![];
+    let mut freed = 0;
+    for (node, alive) in nodes.iter().zip(alive) {
+        if !alive {
+            contents.extend(node.clear());
+            freed += 1;
+        }
+    }
+    drop(contents);
+    drop(nodes);
+
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.tracked.retain(Tracked::is_alive);
+        heap.allocated = 0;
+        heap.threshold = heap.tracked.len().max(MIN_THRESHOLD);
+        heap.collections += 1;
+        heap.freed += freed;
+    });
+    freed
+}
+
+/// An object that can reference other objects.
+enum Node {
+    List(Rc<RefCell<Vec<Value>>>),
+    HashMap(Rc<RefCell<HashMap<String, Value>>>),
+    Closure(Rc<Closure>),
+    Upvalue(Rc<RefCell<Upvalue>>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+    BoundMethod(Rc<BoundMethod>),
+    Generator(Rc<RefCell<Generator>>),
+    Iterator(Rc<RefCell<Iterator>>),
+    Error(Rc<RuntimeError>),
+    Module(Rc<Module>),
+}
+
+impl Node {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Object(object) => match object {
+                Object::List(list) => Some(Self::List(Rc::clone(list))),
+                Object::HashMap(hash_map) => Some(Self::HashMap(Rc::clone(hash_map))),
+                Object::Closure(closure) => Some(Self::Closure(Rc::clone(closure))),
+                Object::Class(class) => Some(Self::Class(Rc::clone(class))),
+                Object::Instance(instance) => Some(Self::Instance(Rc::clone(instance))),
+                Object::BoundMethod(bound_method) => {
+                    Some(Self::BoundMethod(Rc::clone(bound_method)))
+                }
+                Object::Iterator(iterator) => Some(Self::Iterator(Rc::clone(iterator))),
+                Object::Error(err) => Some(Self::Error(Rc::clone(err))),
+                Object::Module(module) => Some(Self::Module(Rc::clone(module))),
+                Object::File(..) | Object::Function(..) | Object::Native(..) => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn id(&self) -> usize {
+        match self {
+            Self::List(list) => Rc::as_ptr(list) as *const () as usize,
+            Self::HashMap(hash_map) => Rc::as_ptr(hash_map) as *const () as usize,
+            Self::Closure(closure) => Rc::as_ptr(closure) as *const () as usize,
+            Self::Upvalue(upvalue) => Rc::as_ptr(upvalue) as *const () as usize,
+            Self::Class(class) => Rc::as_ptr(class) as *const () as usize,
+            Self::Instance(instance) => Rc::as_ptr(instance) as *const () as usize,
+            Self::BoundMethod(bound_method) => Rc::as_ptr(bound_method) as *const () as usize,
+            Self::Generator(generator) => Rc::as_ptr(generator) as *const () as usize,
+            Self::Iterator(iterator) => Rc::as_ptr(iterator) as *const () as usize,
+            Self::Error(err) => Rc::as_ptr(err) as *const () as usize,
+            Self::Module(module) => Rc::as_ptr(module) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Self::List(list) => Rc::strong_count(list),
+            Self::HashMap(hash_map) => Rc::strong_count(hash_map),
+            Self::Closure(closure) => Rc::strong_count(closure),
+            Self::Upvalue(upvalue) => Rc::strong_count(upvalue),
+            Self::Class(class) => Rc::strong_count(class),
+            Self::Instance(instance) => Rc::strong_count(instance),
+            Self::BoundMethod(bound_method) => Rc::strong_count(bound_method),
+            Self::Generator(generator) => Rc::strong_count(generator),
+            Self::Iterator(iterator) => Rc::strong_count(iterator),
+            Self::Error(err) => Rc::strong_count(err),
+            Self::Module(module) => Rc::strong_count(module),
+        }
+    }
+
+    /// The objects this one references, once for every reference it holds.
+    fn children(&self) -> Vec<Node> {
+        fn values<'a>(values: impl std::iter::Iterator<Item = &'a Value>) -> Vec<Node> {
+            values.filter_map(Node::from_value).collect()
+        }
+
+        match self {
+            Self::List(list) => values(list.borrow().iter()),
+            Self::HashMap(hash_map) => values(hash_map.borrow().values()),
+            Self::Closure(closure) => closure
+                .upvalues()
+                .iter()
+                .map(|upvalue| Self::Upvalue(Rc::clone(upvalue)))
+                .collect(),
+            Self::Upvalue(upvalue) => match &*upvalue.borrow() {
+                Upvalue::Open(..) => vec![],
+                Upvalue::Closed(value) => values(std::iter::once(value)),
+            },
+            Self::Class(class) => class
+                .methods()
+                .borrow()
+                .values()
+                .map(|method| Self::Closure(Rc::clone(method)))
+                .collect(),
+            Self::Instance(instance) => {
+                let instance = instance.borrow();
+                let mut children = values(instance.fields().values());
+                children.push(Self::Class(instance.class()));
+                children
+            }
+            Self::BoundMethod(bound_method) => {
+                let mut children = values(std::iter::once(&bound_method.receiver()));
+                children.push(Self::Closure(bound_method.method()));
+                children
+            }
+            Self::Generator(generator) => {
+                let generator = generator.borrow();
+                let mut children = vec![Self::Closure(generator.closure())];
+                if let Some(state) = generator.state() {
+                    children.extend(values(state.locals.iter().chain(&state.tmps)));
+                    children.extend(
+                        state
+                            .upvalues
+                            .iter()
+                            .map(|(_, upvalue)| Self::Upvalue(Rc::clone(upvalue))),
+                    );
+                }
+                children
+            }
+            Self::Iterator(iterator) => match iterator.borrow().iterable() {
+                Iterable::List(list) => vec![Self::List(Rc::clone(list))],
+                Iterable::Generator(generator) => vec![Self::Generator(Rc::clone(generator))],
+                Iterable::String(..) | Iterable::Range(..) => vec![],
+            },
+            Self::Error(err) => match err.as_ref() {
+                RuntimeError::User(value, ..) => values(std::iter::once(value)),
+                _ => vec![],
+            },
+            Self::Module(module) => vec![Self::HashMap(module.exports())],
+        }
+    }
+
+    /// Takes what the object references out of it, if it can be changed.
+    fn clear(&self) -> Vec<Value> {
+        match self {
+            Self::List(list) => list.take(),
+            Self::HashMap(hash_map) => hash_map.take().into_values().collect(),
+            Self::Upvalue(upvalue) => match upvalue.replace(Upvalue::Closed(Value::Nil)) {
+                Upvalue::Closed(value) => vec![value],
+                // Suspended generators close the upvalues of their locals until they're resumed.
+                Upvalue::Open(..) => unreachable!("Open upvalues are referenced by the VM"),
+            },
+            Self::Class(class) => class
+                .methods()
+                .take()
+                .into_values()
+                .map(|method| Value::Object(Object::Closure(method)))
+                .collect(),
+            Self::Instance(instance) => instance.borrow_mut().take_fields(),
+            Self::Generator(generator) => match generator.borrow_mut().take_state() {
+                Some(state) => state.locals.into_iter().chain(state.tmps).collect(),
+                None => vec![],
+            },
+            Self::Closure(..)
+            | Self::BoundMethod(..)
+            | Self::Iterator(..)
+            | Self::Error(..)
+            | Self::Module(..) => vec![],
+        }
+    }
+}
@@ -1,10 +1,11 @@
+pub mod gc;
 pub mod value;
 
 use lexer::token::Token;
 use std::{fmt, rc::Rc};
 use value::{Function, Object, Value};
 
-/// Implements `Into<u8>` and `From<u8> for the enum created inside.
+/// Implements `From<u8>` and `Into<u8>` (through `From<_> for u8`) for the enum created inside.
 ///
 /// Variants aren't expected to have payloads or be more than 256.
 macro_rules! byte_enum {
@@ -16,9 +17,9 @@ macro_rules! byte_enum {
             $($(#[$vmeta])* $vname),*
         }
 
-        impl std::convert::Into<u8> for $name {
-            fn into(self) -> u8 {
-                self as u8
+        impl std::convert::From<$name> for u8 {
+            fn from(v: $name) -> Self {
+                v as u8
             }
         }
 
@@ -51,6 +52,22 @@ byte_enum! {
         DIV,
         /// Implements `Value::rem` on TOT1 and TOT.
         REM,
+        /// Implements `Value::pow` on TOT1 and TOT.
+        POW,
+        /// Implements `Value::floor_div` on TOT1 and TOT.
+        FLOOR_DIV,
+        /// Implements `Value::bitand` on TOT1 and TOT.
+        BIT_AND,
+        /// Implements `Value::bitor` on TOT1 and TOT.
+        BIT_OR,
+        /// Implements `Value::bitxor` on TOT1 and TOT.
+        BIT_XOR,
+        /// Implements `Value::shl` on TOT1 and TOT.
+        SHL,
+        /// Implements `Value::shr` on TOT1 and TOT.
+        SHR,
+        /// Implements `Value::bit_not` on TOT.
+        BIT_NOT,
         /// Uses `Value::eq`.
         EQ,
         NOT_EQ,
@@ -62,6 +79,10 @@ byte_enum! {
         LESS,
         /// Implemnts `Value::partial_cmp` on TOT1 and TOT, accepting `Ordering::Less` or `Ordering::Equal`.
         LESS_EQ,
+        /// Checks whether TOT1 is inside TOT, TOT and TOT1 are popped.
+        ///
+        /// TOT can be a list, in which TOT1 is compared with its elements, a hash map, in which TOT1 must be a string key, or a string, in which TOT1 must be a substring.
+        CONTAINS,
         /// `CONST8 <idx: u8>`
         ///
         /// Pushes `constants[idx]` to tmps.
@@ -82,6 +103,14 @@ byte_enum! {
         ///
         /// Jumps if TOT is true otherwise TOT is popped.
         JUMP_IF_TRUTHY_OR_POP,
+        /// `JUMP_IF_NOT_NIL_OR_POP <offset: u16>`
+        ///
+        /// Jumps if TOT isn't nil otherwise TOT is popped.
+        JUMP_IF_NOT_NIL_OR_POP,
+        /// `JUMP_IF_NIL <offset: u16>`
+        ///
+        /// Jumps if TOT is nil, TOT stays on tmps.
+        JUMP_IF_NIL,
         /// `POP_JUMP_IF_FALSY <offset: u16>`
         ///
         /// Jumps if TOT is false, TOT is popped.
@@ -138,13 +167,13 @@ byte_enum! {
         ///
         /// Sets `globals[constants[idx]]` to TOT, TOT stays on tmps.
         ///
-        /// Fails if `globals[constants[idx]]` is undefined.
+        /// Fails if `globals[constants[idx]]` is undefined or constant.
         SET_GLOBAL8,
         /// `SET_GLOBAL16 <idx: u16>`
         ///
         /// Sets `globals[constants[idx]]` to TOT, TOT stays on tmps.
         ///
-        /// Fails if `globals[constants[idx]]` is undefined.
+        /// Fails if `globals[constants[idx]]` is undefined or constant.
         SET_GLOBAL16,
         /// `DEF_GLOBAL8 <idx: u8>`
         ///
@@ -158,6 +187,14 @@ byte_enum! {
         ///
         /// Fails if `globals[constants[idx]]` is already defined.
         DEF_GLOBAL16,
+        /// `DEF_CONST_GLOBAL8 <idx: u8>`
+        ///
+        /// Same as `DEF_GLOBAL8` but `SET_GLOBAL8` and `SET_GLOBAL16` fail for the defined global.
+        DEF_CONST_GLOBAL8,
+        /// `DEF_CONST_GLOBAL16 <idx: u16>`
+        ///
+        /// Same as `DEF_GLOBAL16` but `SET_GLOBAL8` and `SET_GLOBAL16` fail for the defined global.
+        DEF_CONST_GLOBAL16,
         /// `CLOSURE8 <idx: u8> <upvaluec: u8> <(local: bool, idx: u8)>...`
         ///
         /// Expects TOT to be a `Function`, TOT gets replaced with the result.
@@ -180,10 +217,36 @@ byte_enum! {
         ///
         /// For `Native`s it pops the native and its args from the tmps and invokes it with them pushing the result to tmps.
         CALL,
+        /// Same as `CALL` but with the elements of the list at TOT as the arguments, TOT is popped.
+        ///
+        /// Used when some of the arguments are spread, so their count is only known at runtime.
+        CALL_LIST,
+        /// `IMPORT8 <idx: u8>`
+        ///
+        /// Expects TOT to be the closure of the module at the path `constants[idx]`, TOT gets replaced with the module.
+        ///
+        /// Only the first import of a path calls the closure, the later ones get the module it returned.
+        IMPORT8,
+        /// `IMPORT16 <idx: u16>`
+        ///
+        /// Same as `IMPORT8`.
+        IMPORT16,
+        /// `NATIVE_MODULE8 <idx: u8>`
+        ///
+        /// Pushes the module implemented by the VM with the name `constants[idx]` to tmps.
+        NATIVE_MODULE8,
+        /// `NATIVE_MODULE16 <idx: u16>`
+        ///
+        /// Same as `NATIVE_MODULE8`.
+        NATIVE_MODULE16,
         /// Leaves the required and optional params and reduces the rest into a list.
         BUILD_VARIADIC,
         /// Closes any upvalue associate to one of the closure's locals, pops the locals, and returns TOT, TOT is popped.
         RET,
+        /// Suspends the generator's frame, saving its locals and tmps, and yields TOT, TOT is popped.
+        ///
+        /// Like `RET`, it closes the upvalues associated to the frame's locals.
+        YIELD,
         /// `BUILD_LIST <size: u16>`
         ///
         /// Takes the last `size`th values from tmps and creates a list with them.
@@ -192,6 +255,46 @@ byte_enum! {
         ///
         /// Expects key-value pairs to be on tmps.
         BUILD_HASH_MAP,
+        /// `BUILD_MODULE <size: u16>`
+        ///
+        /// Creates a module out of the exports hash map below the last `size`th values, which are the names of its constants.
+        BUILD_MODULE,
+        /// Creates a class named TOT, TOT is popped.
+        CLASS,
+        /// Copies the methods of the class at TOT to the class at TOT1, TOT is popped.
+        INHERIT,
+        /// Adds the closure at TOT as a method to the class at TOT1, TOT is popped.
+        METHOD,
+        /// Binds the method of the class at TOT1 named TOT to TOT2, TOT, TOT1, and TOT2 are popped.
+        GET_SUPER,
+        /// `MATCH_LIST <size: u16>`
+        ///
+        /// Replaces TOT with whether it's a list of `size` elements.
+        MATCH_LIST,
+        /// `MATCH_LIST_REST <size: u16>`
+        ///
+        /// Replaces TOT with whether it's a list of at least `size` elements.
+        MATCH_LIST_REST,
+        /// Replaces TOT with whether it's a hash map.
+        MATCH_HASH_MAP,
+        /// Replaces the hash map at TOT1 and the key at TOT with whether the hash map has the key.
+        MATCH_KEY,
+        /// `LIST_REST <from: u16>`
+        ///
+        /// Replaces the list at TOT with a new list of its elements starting from `from`.
+        LIST_REST,
+        /// Appends the elements of the list at TOT to the list at TOT1, TOT is popped.
+        EXTEND_LIST,
+        /// Copies the key-value pairs of the hash map at TOT to the hash map at TOT1, TOT is popped.
+        MERGE_HASH_MAP,
+        /// Creates a range from TOT2 (the start), TOT1 (the end), and TOT (the step), TOT, TOT1, and TOT2 are popped.
+        ///
+        /// The end is exclusive, and the step can't be zero.
+        BUILD_RANGE,
+        /// `BUILD_STRING <size: u16>`
+        ///
+        /// Takes the last `size`th values from tmps, converts each of them to a string, and concatenates them.
+        BUILD_STRING,
         /// `GET`
         ///
         /// Implements `TOT1[TOT]`, TOT and TOT1 are popped.
@@ -202,6 +305,10 @@ byte_enum! {
         ///
         /// For strings and lists TOT must be an integer, but for hash maps, It must be a string.
         GET,
+        /// `GET_OPTIONAL`
+        ///
+        /// Works like `GET` except that it results in nil when TOT isn't inside TOT1.
+        GET_OPTIONAL,
         /// `SET`
         ///
         /// Implements `TOT1[TOT] = TOT2`, TOT and TOT1 are popped.
@@ -217,14 +324,20 @@ byte_enum! {
         /// `offset` represents the difference between this instruction and the catch's block start.
         ///
         /// Appends an errors handler which contains the start of the block's slots and the ip of the catch block start.
+        ///
+        /// The caught error is pushed as it is, to be either handed to `CATCH` or `RETHROW`.
         APPEND_HANDLER,
         /// Pops the last handler.
         POP_HANDLER,
         /// Throws TOT.
         THROW,
+        /// Replaces the error at TOT with the value that `أمسك` receives.
+        CATCH,
+        /// Throws the error at TOT again.
+        RETHROW,
         /// Turns TOT into an iterator.
         ///
-        /// Expects TOT to be a string or list.
+        /// Expects TOT to be a string, a list, or an iterator (which is started over).
         ITER,
         /// `UNPACK_LIST <to: u16>`
         ///
@@ -315,6 +428,10 @@ impl Chunk {
         self.bytes.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
     pub fn byte(&self, offset: usize) -> Option<u8> {
         self.bytes.get(offset).copied()
     }
@@ -324,7 +441,7 @@ impl Chunk {
     }
 
     pub fn token(&self, ip: usize) -> Rc<Token> {
-        Rc::clone(&self.tokens[ip].as_ref().unwrap())
+        Rc::clone(self.tokens[ip].as_ref().unwrap())
     }
 
     fn write_op_code(&mut self, op_code: OpCode, token: Rc<Token>) {
@@ -385,7 +502,7 @@ impl Chunk {
         idx
     }
 
-    /// `op_code` must be `NEG`, `NOT`, `ADD`, `SUB`, `MUL`, `DIV`, `REM`, `EQ`, `GREATER`, `GREATER_EQ`, `LESS`, `LESS_EQ`, `DEF_LOCAL`, `GET`, `SET`, `CLOSE_UPVALUE`, `BUILD_VARIADIC`, `RET`, `POP_HANDLER`, `THROW`, `ITER`, `POP`, or `DUP`.
+    /// `op_code` must be `NEG`, `NOT`, `ADD`, `SUB`, `MUL`, `DIV`, `REM`, `POW`, `FLOOR_DIV`, `BIT_AND`, `BIT_OR`, `BIT_XOR`, `SHL`, `SHR`, `BIT_NOT`, `EQ`, `GREATER`, `GREATER_EQ`, `LESS`, `LESS_EQ`, `CONTAINS`, `DEF_LOCAL`, `GET`, `GET_OPTIONAL`, `SET`, `CLOSE_UPVALUE`, `CALL_LIST`, `BUILD_VARIADIC`, `EXTEND_LIST`, `MERGE_HASH_MAP`, `MATCH_HASH_MAP`, `MATCH_KEY`, `CLASS`, `INHERIT`, `METHOD`, `GET_SUPER`, `BUILD_RANGE`, `RET`, `YIELD`, `POP_HANDLER`, `THROW`, `CATCH`, `RETHROW`, `ITER`, `POP`, or `DUP`.
     pub fn write_instr_no_operands(&mut self, op_code: OpCode, token: Rc<Token>) {
         self.write_op_code(op_code, token)
     }
@@ -403,7 +520,7 @@ impl Chunk {
         self.write_byte(idx)
     }
 
-    /// `op_code` must be (`CONST8`, `CONST16`), (`GET_GLOBAL8`, `GET_GLOBAL16`), (`SET_GLOBAL8`, `SET_GLOBAL16`), (`DEF_GLOBAL8`, `DEF_GLOBAL16`), (`GET8`, `GET_16`), or (`SET8`, `SET16`).
+    /// `op_code` must be (`CONST8`, `CONST16`), (`GET_GLOBAL8`, `GET_GLOBAL16`), (`SET_GLOBAL8`, `SET_GLOBAL16`), (`DEF_GLOBAL8`, `DEF_GLOBAL16`), (`DEF_CONST_GLOBAL8`, `DEF_CONST_GLOBAL16`), (`IMPORT8`, `IMPORT16`), (`NATIVE_MODULE8`, `NATIVE_MODULE16`), (`GET8`, `GET_16`), or (`SET8`, `SET16`).
     ///
     /// Fails when the chunk already has 65536 constants.
     pub fn write_instr_const(
@@ -427,7 +544,7 @@ impl Chunk {
         }
     }
 
-    /// `op_code` must be `JUMP`, `POP_JUMP_IF_FALSE`, `POP_JUMP_IF_TRUE`, `JUMP_IF_FALSE_OR_POP`, `JUMP_IF_TRUE_OR_POP`, `FOR_ITER`, or `APPEND_HANDLER`.
+    /// `op_code` must be `JUMP`, `POP_JUMP_IF_FALSE`, `POP_JUMP_IF_TRUE`, `JUMP_IF_FALSE_OR_POP`, `JUMP_IF_TRUE_OR_POP`, `JUMP_IF_NOT_NIL_OR_POP`, `JUMP_IF_NIL`, `FOR_ITER`, or `APPEND_HANDLER`.
     ///
     /// Returns its indx
     pub fn write_jump(&mut self, op_code: OpCode, token: Rc<Token>) -> usize {
@@ -460,7 +577,7 @@ impl Chunk {
     pub fn write_closure(
         &mut self,
         token: Rc<Token>,
-        function: Function,
+        function: Rc<Function>,
         upvalues: Vec<(bool, usize)>,
     ) -> Result<(), ()> {
         self.write_instr_const((CLOSURE8, CLOSURE16), token, Value::from(function))?;
@@ -478,7 +595,7 @@ impl Chunk {
         self.write_byte(argc)
     }
 
-    /// `op_code` must be `BUILD_LIST` or `BUILD_HASH_MAP`.
+    /// `op_code` must be `BUILD_LIST`, `BUILD_HASH_MAP`, `BUILD_MODULE`, or `BUILD_STRING`.
     ///
     /// Fails when `size` is greater than 65535.
     pub fn write_build(
@@ -509,6 +626,19 @@ impl Chunk {
         Ok(())
     }
 
+    /// `op_code` must be `MATCH_LIST`, `MATCH_LIST_REST`, or `LIST_REST`.
+    ///
+    /// Fails when `size` is greater than 65535.
+    pub fn write_list_pattern(
+        &mut self,
+        op_code: OpCode,
+        token: Rc<Token>,
+        size: usize,
+    ) -> Result<(), ()> {
+        self.write_op_code(op_code, token);
+        self.write_two_bytes(size)
+    }
+
     /// Fails when `to` is greater than 65535
     pub fn write_list_unpack(&mut self, token: Rc<Token>, to: usize) -> Result<(), ()> {
         self.write_op_code(UNPACK_LIST, token);
@@ -533,27 +663,42 @@ impl Chunk {
         }
         let op_code = self.byte(ip)?.into();
         match op_code {
-            NEG | NOT | ADD | SUB | MUL | DIV | REM | EQ | NOT_EQ | GREATER | GREATER_EQ | LESS
-            | LESS_EQ | POP_LOCAL | CLOSE_UPVALUE | BUILD_VARIADIC | RET | POP_HANDLER | THROW
-            | ITER | POP | DUP | GET | SET | DEF_LOCAL => {
+            NEG | NOT | ADD | SUB | MUL | DIV | REM | POW | FLOOR_DIV | BIT_AND | BIT_OR
+            | BIT_XOR | SHL | SHR | BIT_NOT | EQ | NOT_EQ | GREATER | GREATER_EQ | LESS
+            | LESS_EQ | CONTAINS | BUILD_RANGE | POP_LOCAL | CLOSE_UPVALUE | BUILD_VARIADIC
+            | CALL_LIST | EXTEND_LIST | MERGE_HASH_MAP | MATCH_HASH_MAP | MATCH_KEY | CLASS
+            | INHERIT | METHOD | GET_SUPER | RET | YIELD | POP_HANDLER | THROW | CATCH
+            | RETHROW | ITER | POP | DUP | GET | GET_OPTIONAL | SET | DEF_LOCAL => {
                 Some(Instruction::new(op_code, operands!(1)))
             }
             GET_LOCAL | SET_LOCAL | GET_UPVALUE | SET_UPVALUE | CONST8 | GET_GLOBAL8
-            | SET_GLOBAL8 | DEF_GLOBAL8 | CALL => Some(Instruction::new(op_code, operands!(2))),
+            | SET_GLOBAL8 | DEF_GLOBAL8 | DEF_CONST_GLOBAL8 | IMPORT8 | NATIVE_MODULE8 | CALL => {
+                Some(Instruction::new(op_code, operands!(2)))
+            }
             CONST16
             | GET_GLOBAL16
             | SET_GLOBAL16
             | DEF_GLOBAL16
+            | DEF_CONST_GLOBAL16
+            | IMPORT16
+            | NATIVE_MODULE16
             | JUMP
             | POP_JUMP_IF_FALSY
             | POP_JUMP_IF_TRUTHY
             | JUMP_IF_FALSY_OR_POP
             | JUMP_IF_TRUTHY_OR_POP
+            | JUMP_IF_NOT_NIL_OR_POP
+            | JUMP_IF_NIL
             | FOR_ITER
             | APPEND_HANDLER
             | LOOP
             | BUILD_LIST
             | BUILD_HASH_MAP
+            | BUILD_MODULE
+            | BUILD_STRING
+            | MATCH_LIST
+            | MATCH_LIST_REST
+            | LIST_REST
             | UNPACK_LIST => Some(Instruction::new(op_code, operands!(3))),
             UNPACK_HASH_MAP => Some(Instruction::new(op_code, operands!(3 + two_bytes_oper!()))),
             CLOSURE8 => Some(Instruction::new(op_code, operands!(3 + byte_oper!(1) * 2))),
@@ -568,18 +713,23 @@ impl Chunk {
         let mut buf = String::new();
         buf += format!("{:>5} {:20}", ip, format!("{:?}", instr.op_code())).as_str();
         match instr.op_code() {
-            NEG | NOT | ADD | SUB | MUL | DIV | REM | EQ | NOT_EQ | GREATER | GREATER_EQ | LESS
-            | LESS_EQ | POP_LOCAL | CLOSE_UPVALUE | BUILD_VARIADIC | RET | POP_HANDLER | THROW
-            | ITER | POP | DUP | GET | SET => {}
+            NEG | NOT | ADD | SUB | MUL | DIV | REM | POW | FLOOR_DIV | BIT_AND | BIT_OR
+            | BIT_XOR | SHL | SHR | BIT_NOT | EQ | NOT_EQ | GREATER | GREATER_EQ | LESS
+            | LESS_EQ | CONTAINS | BUILD_RANGE | POP_LOCAL | CLOSE_UPVALUE | BUILD_VARIADIC
+            | CALL_LIST | EXTEND_LIST | MERGE_HASH_MAP | MATCH_HASH_MAP | MATCH_KEY | CLASS
+            | INHERIT | METHOD | GET_SUPER | RET | YIELD | POP_HANDLER | THROW | CATCH
+            | RETHROW | ITER | POP | DUP | GET | GET_OPTIONAL | SET => {}
             DEF_LOCAL => buf += format!(" ({})", token.lexeme()).as_str(),
             GET_LOCAL | SET_LOCAL | GET_UPVALUE | SET_UPVALUE => {
                 buf += format!(" {} ({})", instr.read_byte_oper(0), token.lexeme()).as_str()
             }
-            CONST8 | GET_GLOBAL8 | SET_GLOBAL8 | DEF_GLOBAL8 => {
+            CONST8 | GET_GLOBAL8 | SET_GLOBAL8 | DEF_GLOBAL8 | DEF_CONST_GLOBAL8 | IMPORT8
+            | NATIVE_MODULE8 => {
                 let idx = instr.read_byte_oper(0);
                 buf += format!(" {idx} ({})", self.constant(idx)).as_str()
             }
-            CONST16 | GET_GLOBAL16 | SET_GLOBAL16 | DEF_GLOBAL16 => {
+            CONST16 | GET_GLOBAL16 | SET_GLOBAL16 | DEF_GLOBAL16 | DEF_CONST_GLOBAL16
+            | IMPORT16 | NATIVE_MODULE16 => {
                 let idx = instr.read_two_bytes_oper(0);
                 buf += format!(" {idx} ({})", self.constant(idx)).as_str()
             }
@@ -588,6 +738,8 @@ impl Chunk {
             | POP_JUMP_IF_TRUTHY
             | JUMP_IF_FALSY_OR_POP
             | JUMP_IF_TRUTHY_OR_POP
+            | JUMP_IF_NOT_NIL_OR_POP
+            | JUMP_IF_NIL
             | FOR_ITER
             | APPEND_HANDLER => {
                 let offset = instr.read_two_bytes_oper(0);
@@ -615,7 +767,8 @@ impl Chunk {
                 let argc = instr.read_byte_oper(0);
                 buf += format!(" {argc}").as_str()
             }
-            BUILD_LIST | BUILD_HASH_MAP => {
+            BUILD_LIST | BUILD_HASH_MAP | BUILD_MODULE | BUILD_STRING | MATCH_LIST
+            | MATCH_LIST_REST | LIST_REST => {
                 let size = instr.read_two_bytes_oper(0);
                 buf += format!(" {size}").as_str()
             }
@@ -636,6 +789,12 @@ impl Chunk {
     }
 }
 
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl fmt::Debug for Chunk {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut ip = 0;
@@ -656,12 +815,9 @@ impl fmt::Debug for Chunk {
             }
         }
         for constant in &self.constants {
-            match constant {
-                Value::Object(Object::Function(function)) => {
-                    writeln!(f, "\n[CHUNK] {function}'s chunk")?;
-                    write!(f, "{:?}", function.chunk())?
-                }
-                _ => {}
+            if let Value::Object(Object::Function(function)) = constant {
+                writeln!(f, "\n[CHUNK] {function}'s chunk")?;
+                write!(f, "{:?}", function.chunk())?
             }
         }
         Ok(())
@@ -1,7 +1,10 @@
+use super::gc::{self, Tracked};
 use super::Chunk;
 use crate::error::RuntimeError;
+use lexer::token::Token;
+use std::collections::{HashMap, HashSet};
 use std::convert::{From, Into, TryFrom};
-use std::{cell::RefCell, cmp, collections::HashMap, fmt, fs, iter, ops, rc::Rc};
+use std::{cell::RefCell, cmp, fmt, fs, iter, ops, rc::Rc};
 
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -25,6 +28,11 @@ pub enum DataType {
     Closure,
     Native,
     Iterator,
+    Class,
+    Instance,
+    BoundMethod,
+    Error,
+    Module,
 }
 
 impl fmt::Display for DataType {
@@ -44,6 +52,11 @@ impl fmt::Display for DataType {
                 Self::Closure => "دالة",
                 Self::Native => "دالة مدمجة",
                 Self::Iterator => "مكرر",
+                Self::Class => "صنف",
+                Self::Instance => "نسخة",
+                Self::BoundMethod => "دالة مربوطة",
+                Self::Error => "خطأ",
+                Self::Module => "وحدة",
             }
         )
     }
@@ -55,9 +68,9 @@ impl Value {
         match self {
             Self::Nil | Self::Bool(false) => false,
             Self::Number(number) if *number == 0.0 => false,
-            Self::String(string) if string.len() == 0 => false,
-            Self::Object(Object::List(list)) if list.borrow().len() == 0 => false,
-            Self::Object(Object::HashMap(hash_map)) if hash_map.borrow().len() == 0 => false,
+            Self::String(string) if string.is_empty() => false,
+            Self::Object(Object::List(list)) if list.borrow().is_empty() => false,
+            Self::Object(Object::HashMap(hash_map)) if hash_map.borrow().is_empty() => false,
             _ => true,
         }
     }
@@ -75,6 +88,11 @@ impl Value {
             Self::Object(Object::Closure(..)) => DataType::Closure,
             Self::Object(Object::Native(..)) => DataType::Native,
             Self::Object(Object::Iterator(..)) => DataType::Iterator,
+            Self::Object(Object::Class(..)) => DataType::Class,
+            Self::Object(Object::Instance(..)) => DataType::Instance,
+            Self::Object(Object::BoundMethod(..)) => DataType::BoundMethod,
+            Self::Object(Object::Error(..)) => DataType::Error,
+            Self::Object(Object::Module(..)) => DataType::Module,
         }
     }
 }
@@ -95,76 +113,167 @@ impl PartialEq for Value {
     }
 }
 
+/// Why an operator couldn't be applied on its operands.
+#[derive(Debug)]
+pub enum OperationError {
+    /// The types the operator expects and the types of the operands it got instead.
+    Type(Vec<DataType>, Vec<DataType>),
+    /// The number with a fractional part that a bitwise operator got.
+    NotInteger(f64),
+}
+
+/// What the operators on values return.
+pub type OperationResult = Result<Value, OperationError>;
+
+impl Value {
+    /// Applies `f` on the operands when both of them are numbers.
+    fn numeric(self, other: Self, f: fn(f64, f64) -> f64) -> OperationResult {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => Ok(Self::Number(f(a, b))),
+            (a, b) => Err(OperationError::Type(
+                vec![DataType::Number],
+                vec![a.typ(), b.typ()],
+            )),
+        }
+    }
+
+    /// Fails when `number` has a fractional part.
+    fn integer(number: f64) -> Result<i64, OperationError> {
+        if number.fract() == 0.0 {
+            Ok(number as i64)
+        } else {
+            Err(OperationError::NotInteger(number))
+        }
+    }
+
+    /// Applies `f` on the operands when both of them are numbers without fractional parts.
+    fn bitwise(self, other: Self, f: fn(i64, i64) -> i64) -> OperationResult {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => {
+                Ok(Self::Number(f(Self::integer(a)?, Self::integer(b)?) as f64))
+            }
+            (a, b) => Err(OperationError::Type(
+                vec![DataType::Number],
+                vec![a.typ(), b.typ()],
+            )),
+        }
+    }
+}
+
 impl ops::Neg for Value {
-    type Output = Self;
+    type Output = OperationResult;
 
     fn neg(self) -> Self::Output {
         match self {
-            Self::Number(number) => Self::Number(-number),
-            _ => unreachable!(),
+            Self::Number(number) => Ok(Self::Number(-number)),
+            value => Err(OperationError::Type(vec![DataType::Number], vec![value.typ()])),
         }
     }
 }
 
 impl ops::Add for Value {
-    type Output = Self;
+    type Output = OperationResult;
 
-    /// Adds numbers and concatinates sequences.
+    /// Adds numbers and concatinates sequences of the same type.
     fn add(self, other: Self) -> Self::Output {
         match (self, other) {
-            (Self::Number(a), Self::Number(b)) => Self::Number(a + b),
-            (Self::String(a), Self::String(b)) => Self::String(format!("{a}{b}")),
+            (Self::Number(a), Self::Number(b)) => Ok(Self::Number(a + b)),
+            (Self::String(a), Self::String(b)) => Ok(Self::String(format!("{a}{b}"))),
             (Self::Object(Object::List(a)), Self::Object(Object::List(b))) => {
                 let a = a.borrow().clone();
                 let b = b.borrow().clone();
-                Self::from([a, b].concat())
+                Ok(Self::from([a, b].concat()))
             }
-            _ => unreachable!(),
+            (a, b) => Err(OperationError::Type(
+                vec![DataType::Number, DataType::String, DataType::List],
+                vec![a.typ(), b.typ()],
+            )),
         }
     }
 }
 
 impl ops::Sub for Value {
-    type Output = Self;
+    type Output = OperationResult;
 
     fn sub(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Self::Number(a), Self::Number(b)) => Self::Number(a - b),
-            _ => unreachable!(),
-        }
+        self.numeric(other, |a, b| a - b)
     }
 }
 
 impl ops::Mul for Value {
-    type Output = Self;
+    type Output = OperationResult;
 
     fn mul(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Self::Number(a), Self::Number(b)) => Self::Number(a * b),
-            _ => unreachable!(),
-        }
+        self.numeric(other, |a, b| a * b)
     }
 }
 
 impl ops::Div for Value {
-    type Output = Self;
+    type Output = OperationResult;
 
     fn div(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Self::Number(a), Self::Number(b)) => Self::Number(a / b),
-            _ => unreachable!(),
-        }
+        self.numeric(other, |a, b| a / b)
     }
 }
 
 impl ops::Rem for Value {
-    type Output = Self;
+    type Output = OperationResult;
 
     fn rem(self, other: Self) -> Self::Output {
-        match (self, other) {
-            (Self::Number(a), Self::Number(b)) => Self::Number(a % b),
-            _ => unreachable!(),
-        }
+        self.numeric(other, |a, b| a % b)
+    }
+}
+
+// Bitwise operations work on 64-bit integers.
+impl ops::BitAnd for Value {
+    type Output = OperationResult;
+
+    fn bitand(self, other: Self) -> Self::Output {
+        self.bitwise(other, |a, b| a & b)
+    }
+}
+
+impl ops::BitOr for Value {
+    type Output = OperationResult;
+
+    fn bitor(self, other: Self) -> Self::Output {
+        self.bitwise(other, |a, b| a | b)
+    }
+}
+
+impl ops::BitXor for Value {
+    type Output = OperationResult;
+
+    fn bitxor(self, other: Self) -> Self::Output {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+}
+
+impl ops::Shl for Value {
+    type Output = OperationResult;
+
+    /// Shifting by a negative amount or by 64 and more results in 0.
+    fn shl(self, other: Self) -> Self::Output {
+        self.bitwise(other, |a, b| {
+            u32::try_from(b)
+                .ok()
+                .and_then(|b| a.checked_shl(b))
+                .unwrap_or(0)
+        })
+    }
+}
+
+impl ops::Shr for Value {
+    type Output = OperationResult;
+
+    /// Shifts arithmetically, so shifting by a negative amount or by 64 and more results in 0 for positive numbers and -1 for negative ones.
+    fn shr(self, other: Self) -> Self::Output {
+        self.bitwise(other, |a, b| {
+            u32::try_from(b)
+                .ok()
+                .and_then(|b| a.checked_shr(b))
+                .unwrap_or(a >> 63)
+        })
     }
 }
 
@@ -176,10 +285,42 @@ impl ops::Not for Value {
     }
 }
 
+impl Value {
+    pub fn pow(self, other: Self) -> OperationResult {
+        self.numeric(other, f64::powf)
+    }
+
+    pub fn floor_div(self, other: Self) -> OperationResult {
+        self.numeric(other, |a, b| (a / b).floor())
+    }
+
+    pub fn bit_not(self) -> OperationResult {
+        match self {
+            Self::Number(number) => Ok(Self::Number(!Self::integer(number)? as f64)),
+            value => Err(OperationError::Type(vec![DataType::Number], vec![value.typ()])),
+        }
+    }
+
+    /// Orders two numbers or two strings, where `None` is returned when one of the numbers is NaN.
+    pub fn compare(&self, other: &Self) -> Result<Option<cmp::Ordering>, OperationError> {
+        match (self, other) {
+            (Self::Number(..), Self::Number(..)) | (Self::String(..), Self::String(..)) => {
+                Ok(self.partial_cmp(other))
+            }
+            (a, b) => Err(OperationError::Type(
+                vec![DataType::Number, DataType::String],
+                vec![a.typ(), b.typ()],
+            )),
+        }
+    }
+}
+
 impl PartialOrd for Value {
+    /// Numbers are ordered by value and strings are ordered lexicographically by their characters.
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
         match (self, other) {
             (Self::Number(a), Self::Number(b)) => a.partial_cmp(b),
+            (Self::String(a), Self::String(b)) => a.chars().partial_cmp(b.chars()),
             _ => None,
         }
     }
@@ -241,13 +382,17 @@ impl From<char> for Value {
 
 impl From<HashMap<String, Value>> for Value {
     fn from(hash_map: HashMap<String, Value>) -> Self {
-        Self::Object(Object::HashMap(Rc::new(RefCell::new(hash_map))))
+        let hash_map = Rc::new(RefCell::new(hash_map));
+        gc::track(Tracked::HashMap(Rc::downgrade(&hash_map)));
+        Self::Object(Object::HashMap(hash_map))
     }
 }
 
 impl From<Vec<Value>> for Value {
     fn from(list: Vec<Value>) -> Self {
-        Self::Object(Object::List(Rc::new(RefCell::new(list))))
+        let list = Rc::new(RefCell::new(list));
+        gc::track(Tracked::List(Rc::downgrade(&list)));
+        Self::Object(Object::List(list))
     }
 }
 
@@ -263,6 +408,12 @@ impl From<Function> for Value {
     }
 }
 
+impl From<Rc<Function>> for Value {
+    fn from(function: Rc<Function>) -> Self {
+        Self::Object(Object::Function(function))
+    }
+}
+
 impl From<Closure> for Value {
     fn from(closure: Closure) -> Self {
         Self::Object(Object::Closure(Rc::new(closure)))
@@ -275,6 +426,32 @@ impl From<Native> for Value {
     }
 }
 
+impl From<Class> for Value {
+    fn from(class: Class) -> Self {
+        Self::Object(Object::Class(Rc::new(class)))
+    }
+}
+
+impl From<Instance> for Value {
+    fn from(instance: Instance) -> Self {
+        let instance = Rc::new(RefCell::new(instance));
+        gc::track(Tracked::Instance(Rc::downgrade(&instance)));
+        Self::Object(Object::Instance(instance))
+    }
+}
+
+impl From<BoundMethod> for Value {
+    fn from(bound_method: BoundMethod) -> Self {
+        Self::Object(Object::BoundMethod(Rc::new(bound_method)))
+    }
+}
+
+impl From<Module> for Value {
+    fn from(module: Module) -> Self {
+        Self::Object(Object::Module(Rc::new(module)))
+    }
+}
+
 impl From<Iterable> for Value {
     fn from(iterable: Iterable) -> Self {
         Self::Object(Object::Iterator(Rc::new(RefCell::new(Iterator::from(
@@ -344,6 +521,28 @@ impl TryInto<Rc<Function>> for Value {
     }
 }
 
+impl TryInto<Rc<Closure>> for Value {
+    type Error = ();
+
+    fn try_into(self) -> Result<Rc<Closure>, Self::Error> {
+        match self {
+            Self::Object(Object::Closure(closure)) => Ok(closure),
+            _ => Err(()),
+        }
+    }
+}
+
+impl TryInto<Rc<Class>> for Value {
+    type Error = ();
+
+    fn try_into(self) -> Result<Rc<Class>, Self::Error> {
+        match self {
+            Self::Object(Object::Class(class)) => Ok(class),
+            _ => Err(()),
+        }
+    }
+}
+
 impl TryInto<Rc<RefCell<Iterator>>> for Value {
     type Error = ();
 
@@ -364,6 +563,12 @@ pub enum Object {
     Closure(Rc<Closure>),
     Native(Rc<Native>),
     Iterator(Rc<RefCell<Iterator>>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+    BoundMethod(Rc<BoundMethod>),
+    /// A caught error, which is what `أمسك` receives for the built-in errors.
+    Error(Rc<RuntimeError>),
+    Module(Rc<Module>),
 }
 
 impl PartialEq for Object {
@@ -376,6 +581,11 @@ impl PartialEq for Object {
             (Self::Closure(a), Self::Closure(b)) => Rc::ptr_eq(a, b),
             (Self::Native(a), Self::Native(b)) => Rc::ptr_eq(a, b),
             (Self::Iterator(a), Self::Iterator(b)) => Rc::ptr_eq(a, b),
+            (Self::Class(a), Self::Class(b)) => Rc::ptr_eq(a, b),
+            (Self::Instance(a), Self::Instance(b)) => Rc::ptr_eq(a, b),
+            (Self::BoundMethod(a), Self::BoundMethod(b)) => Rc::ptr_eq(a, b),
+            (Self::Error(a), Self::Error(b)) => Rc::ptr_eq(a, b),
+            (Self::Module(a), Self::Module(b)) => Rc::ptr_eq(a, b),
             _ => false,
         }
     }
@@ -390,8 +600,8 @@ impl fmt::Display for Object {
                 write!(f, "{{")?;
                 if let Some(key) = iter.next() {
                     write!(f, "{key}: {}", tmp.get(key).unwrap())?;
-                    while let Some(key) = iter.next() {
-                        write!(f, "{key}: {}", tmp.get(key).unwrap())?;
+                    for key in iter {
+                        write!(f, "، {key}: {}", tmp.get(key).unwrap())?;
                     }
                 }
                 write!(f, "}}")
@@ -402,7 +612,7 @@ impl fmt::Display for Object {
                 write!(f, "[")?;
                 if let Some(value) = iter.next() {
                     write!(f, "{value}")?;
-                    while let Some(value) = iter.next() {
+                    for value in iter {
                         write!(f, "، {value}")?;
                     }
                 }
@@ -413,6 +623,11 @@ impl fmt::Display for Object {
             Self::Closure(closure) => write!(f, "{}", closure.function),
             Self::Native(native) => write!(f, "<{native:?}دالة مدمجة مختزنة في >"),
             Self::Iterator(iterator) => write!(f, "{}", iterator.borrow()),
+            Self::Class(class) => write!(f, "{class}"),
+            Self::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Self::BoundMethod(bound_method) => write!(f, "{bound_method}"),
+            Self::Error(err) => write!(f, "{}", err.msg()),
+            Self::Module(module) => write!(f, "{}", Object::HashMap(module.exports())),
         }
     }
 }
@@ -421,6 +636,7 @@ impl fmt::Display for Object {
 pub struct File {
     name: String,
     mode: FileMode,
+    #[allow(dead_code)]
     file: fs::File,
 }
 
@@ -461,12 +677,12 @@ impl TryFrom<String> for FileMode {
     }
 }
 
-impl Into<String> for FileMode {
-    fn into(self) -> String {
-        match self {
-            Self::Read => READ.to_owned(),
-            Self::Write => WRITE.to_owned(),
-            Self::All => ALL.to_owned(),
+impl From<FileMode> for String {
+    fn from(mode: FileMode) -> Self {
+        match mode {
+            FileMode::Read => READ.to_owned(),
+            FileMode::Write => WRITE.to_owned(),
+            FileMode::All => ALL.to_owned(),
         }
     }
 }
@@ -486,6 +702,8 @@ pub struct Function {
     defaults: Vec<usize>,
     /// Represents the `ip` of the first instruction in the variadic param builder (if the function is variadic) or the code for destructuring otherwise.
     body: usize,
+    /// Whether calling it creates a generator instead of running it.
+    generator: bool,
 }
 
 impl Function {
@@ -495,6 +713,7 @@ impl Function {
         arity: Arity,
         defaults: Vec<usize>,
         body: usize,
+        generator: bool,
     ) -> Self {
         Self {
             name,
@@ -502,6 +721,7 @@ impl Function {
             arity,
             defaults,
             body,
+            generator,
         }
     }
 
@@ -572,6 +792,14 @@ pub enum Upvalue {
     Closed(Value),
 }
 
+impl Upvalue {
+    pub fn open(idx: usize) -> Rc<RefCell<Self>> {
+        let upvalue = Rc::new(RefCell::new(Self::Open(idx)));
+        gc::track(Tracked::Upvalue(Rc::downgrade(&upvalue)));
+        upvalue
+    }
+}
+
 impl TryInto<usize> for Upvalue {
     type Error = ();
 
@@ -606,10 +834,18 @@ impl Closure {
         self.function.chunk()
     }
 
+    pub fn is_generator(&self) -> bool {
+        self.function.generator
+    }
+
     pub fn upvalue(&self, idx: usize) -> Rc<RefCell<Upvalue>> {
         Rc::clone(&self.upvalues[idx])
     }
 
+    pub(super) fn upvalues(&self) -> &[Rc<RefCell<Upvalue>>] {
+        &self.upvalues
+    }
+
     /// Returns where the function should start executing giving `argc`.
     pub fn start_ip(&self, argc: usize) -> usize {
         let Arity {
@@ -618,10 +854,10 @@ impl Closure {
             optional,
         } = self.function.arity.clone();
         match argc {
-            x if x == required => self.function.body,
-            x if x > required && x <= required + optional => {
-                self.function.defaults[argc - required - 1]
+            x if x >= required && x < required + optional => {
+                self.function.defaults[argc - required]
             }
+            x if x == required + optional => self.function.body,
             x if x > required + optional && typ == ArityType::Variadic => self.function.body,
             _ => unreachable!(),
         }
@@ -631,13 +867,170 @@ impl Closure {
 impl From<Chunk> for Closure {
     fn from(chunk: Chunk) -> Self {
         Self {
-            function: Rc::new(Function::new(None, chunk, Arity::default(), vec![], 0)),
+            function: Rc::new(Function::new(
+                None,
+                chunk,
+                Arity::default(),
+                vec![],
+                0,
+                false,
+            )),
             upvalues: vec![],
         }
     }
 }
 
-type NativeFn = fn(Vec<Value>) -> Result<Value, RuntimeError>;
+/// The name of the method that is called when creating instances.
+pub const CONSTRUCTOR: &str = "أنشئ";
+
+#[derive(Debug)]
+pub struct Class {
+    name: String,
+    methods: RefCell<HashMap<String, Rc<Closure>>>,
+}
+
+impl Class {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            methods: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn method(&self, name: &str) -> Option<Rc<Closure>> {
+        self.methods.borrow().get(name).cloned()
+    }
+
+    /// `method` must have a name.
+    pub fn add_method(&self, method: Rc<Closure>) {
+        self.methods
+            .borrow_mut()
+            .insert(method.name().unwrap(), method);
+    }
+
+    pub(super) fn methods(&self) -> &RefCell<HashMap<String, Rc<Closure>>> {
+        &self.methods
+    }
+
+    /// Copies the methods of `parent`, so it must be called before adding the class's own methods.
+    pub fn inherit(&self, parent: &Class) {
+        let methods = parent.methods.borrow().clone();
+        self.methods.borrow_mut().extend(methods);
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<صنف {}>", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    class: Rc<Class>,
+    fields: HashMap<String, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn class(&self) -> Rc<Class> {
+        Rc::clone(&self.class)
+    }
+
+    pub fn field(&self, name: &str) -> Option<Value> {
+        self.fields.get(name).cloned()
+    }
+
+    pub fn set_field(&mut self, name: String, value: Value) {
+        self.fields.insert(name, value);
+    }
+
+    pub(super) fn fields(&self) -> &HashMap<String, Value> {
+        &self.fields
+    }
+
+    pub(super) fn take_fields(&mut self) -> Vec<Value> {
+        self.fields.drain().map(|(_, value)| value).collect()
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<نسخة من صنف {} مختزنة في {:?}>",
+            self.class.name, self as *const Self
+        )
+    }
+}
+
+/// The exports of a module, the constant ones of which can't be set by its importers.
+#[derive(Debug)]
+pub struct Module {
+    exports: Rc<RefCell<HashMap<String, Value>>>,
+    constants: HashSet<String>,
+}
+
+impl Module {
+    pub fn new(exports: Rc<RefCell<HashMap<String, Value>>>, constants: HashSet<String>) -> Self {
+        Self { exports, constants }
+    }
+
+    pub fn exports(&self) -> Rc<RefCell<HashMap<String, Value>>> {
+        Rc::clone(&self.exports)
+    }
+
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.constants.contains(name)
+    }
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    receiver: Value,
+    method: Rc<Closure>,
+}
+
+impl BoundMethod {
+    pub fn new(receiver: Value, method: Rc<Closure>) -> Self {
+        Self { receiver, method }
+    }
+
+    pub fn receiver(&self) -> Value {
+        self.receiver.clone()
+    }
+
+    pub fn method(&self) -> Rc<Closure> {
+        Rc::clone(&self.method)
+    }
+}
+
+impl fmt::Display for BoundMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<دالة {} مربوطة بنسخة من صنف {}>",
+            self.method.name().unwrap(),
+            match &self.receiver {
+                Value::Object(Object::Instance(instance)) => instance.borrow().class.name.clone(),
+                _ => unreachable!(),
+            }
+        )
+    }
+}
+
+/// Takes the native followed by its arguments, and the token of the call.
+type NativeFn = fn(Vec<Value>, Rc<Token>) -> Result<Value, RuntimeError>;
 
 #[derive(Debug, Clone)]
 pub struct Native {
@@ -650,8 +1043,8 @@ impl Native {
         Self { function, arity }
     }
 
-    pub fn call(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
-        (self.function)(args)
+    pub fn call(&self, args: Vec<Value>, token: Rc<Token>) -> Result<Value, RuntimeError> {
+        (self.function)(args, token)
     }
 
     pub fn arity(&self) -> &Arity {
@@ -659,19 +1052,101 @@ impl Native {
     }
 }
 
+/// The state of a suspended generator's frame.
+#[derive(Debug)]
+pub struct GeneratorState {
+    pub ip: usize,
+    pub locals: Vec<Value>,
+    pub tmps: Vec<Value>,
+    /// ip, slots, tmps: where the slots and tmps are relative to the generator's own.
+    pub handlers: Vec<(usize, usize, usize)>,
+    /// The upvalues of `locals` with their indices in it, which are closed while the generator is suspended and opened
+    /// again when it's resumed.
+    pub upvalues: Vec<(usize, Rc<RefCell<Upvalue>>)>,
+}
+
+#[derive(Debug)]
+pub struct Generator {
+    closure: Rc<Closure>,
+    argc: usize,
+    /// `None` while the generator is running or after it finishes.
+    state: Option<GeneratorState>,
+}
+
+impl Generator {
+    /// `tmps` must be the closure followed by its arguments, as they are before calling it.
+    pub fn new(closure: Rc<Closure>, argc: usize, tmps: Vec<Value>) -> Self {
+        let ip = closure.start_ip(argc);
+        Self {
+            closure,
+            argc,
+            state: Some(GeneratorState {
+                ip,
+                locals: vec![],
+                tmps,
+                handlers: vec![],
+                upvalues: vec![],
+            }),
+        }
+    }
+
+    pub fn closure(&self) -> Rc<Closure> {
+        Rc::clone(&self.closure)
+    }
+
+    pub fn argc(&self) -> usize {
+        self.argc
+    }
+
+    /// Takes the state to resume the generator with, leaving it `None` until it's suspended again.
+    pub fn take_state(&mut self) -> Option<GeneratorState> {
+        self.state.take()
+    }
+
+    pub fn suspend(&mut self, state: GeneratorState) {
+        self.state = Some(state)
+    }
+
+    pub(super) fn state(&self) -> Option<&GeneratorState> {
+        self.state.as_ref()
+    }
+}
+
 #[derive(Debug)]
 pub struct Iterator {
     iterable: Iterable,
     counter: usize,
 }
 
+impl Iterator {
+    pub(super) fn iterable(&self) -> &Iterable {
+        &self.iterable
+    }
+
+    pub fn generator(&self) -> Option<Rc<RefCell<Generator>>> {
+        match &self.iterable {
+            Iterable::Generator(generator) => Some(Rc::clone(generator)),
+            _ => None,
+        }
+    }
+}
+
 impl iter::Iterator for Iterator {
     type Item = Value;
 
     fn next(&mut self) -> Option<Self::Item> {
         let item = match &self.iterable {
             Iterable::List(list) => list.borrow().get(self.counter).cloned(),
-            Iterable::String(s) => s.chars().nth(self.counter).map(|ch| Value::from(ch)),
+            Iterable::String(s) => s.chars().nth(self.counter).map(Value::from),
+            Iterable::Range(start, end, step) => {
+                let value = start + self.counter as f64 * step;
+                if (*step > 0.0 && value < *end) || (*step < 0.0 && value > *end) {
+                    Some(Value::Number(value))
+                } else {
+                    None
+                }
+            }
+            Iterable::Generator(..) => unreachable!("Generators are resumed by the VM"),
         };
         self.counter += 1;
         item
@@ -680,7 +1155,11 @@ impl iter::Iterator for Iterator {
 
 impl fmt::Display for Iterator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "<مكرر مختزن في {:?}>", self as *const Self)
+        match self.iterable {
+            Iterable::Range(start, end, 1.0) => write!(f, "{start}..{end}"),
+            Iterable::Range(start, end, step) => write!(f, "{start}..{end}..{step}"),
+            _ => write!(f, "<مكرر مختزن في {:?}>", self as *const Self),
+        }
     }
 }
 
@@ -688,6 +1167,10 @@ impl fmt::Display for Iterator {
 pub enum Iterable {
     List(Rc<RefCell<Vec<Value>>>),
     String(String),
+    /// start, end, step
+    Range(f64, f64, f64),
+    /// Advanced by the VM since it has to run the generator's frame.
+    Generator(Rc<RefCell<Generator>>),
 }
 
 impl From<Iterable> for Iterator {
@@ -699,6 +1182,14 @@ impl From<Iterable> for Iterator {
     }
 }
 
+impl From<Generator> for Iterable {
+    fn from(generator: Generator) -> Self {
+        let generator = Rc::new(RefCell::new(generator));
+        gc::track(Tracked::Generator(Rc::downgrade(&generator)));
+        Self::Generator(generator)
+    }
+}
+
 impl TryFrom<Value> for Iterable {
     type Error = ();
 
@@ -706,6 +1197,8 @@ impl TryFrom<Value> for Iterable {
         match value {
             Value::String(s) => Ok(Self::String(s)),
             Value::Object(Object::List(list)) => Ok(Self::List(list)),
+            // Iterating over an iterator starts it over, so that ranges can be iterated more than once.
+            Value::Object(Object::Iterator(iterator)) => Ok(iterator.borrow().iterable.clone()),
             _ => Err(()),
         }
     }
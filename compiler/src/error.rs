@@ -1,7 +1,8 @@
-use super::value::{Arity, DataType, Value};
+use super::value::{Arity, DataType, Object, Value};
+use crate::NATIVE_MODULES;
 use colored::Colorize;
 use lexer::token::*;
-use std::{fmt, io, rc::Rc};
+use std::{collections::HashMap, fmt, io, path::PathBuf, rc::Rc};
 
 #[derive(Debug, Clone)]
 pub enum CompileError {
@@ -22,7 +23,16 @@ pub enum CompileError {
     InvalidExportUsage(Rc<Token>),
     Io(Rc<Token>, Rc<io::Error>),
     ModuleParser(Rc<Token>, Vec<parser::error::Error>),
+    /// The paths of the modules that import each other, starting and ending with the same module.
+    ImportCycle(Rc<Token>, Vec<PathBuf>),
+    UnknownNativeModule(Rc<Token>, String),
     TooManyArgs(Rc<Token>),
+    InvalidPattern(Rc<Token>),
+    OutsideClass(Rc<Token>),
+    YieldOutsideFunction(Rc<Token>),
+    UnknownErrorKind(Rc<Token>),
+    UnknownLabel(Rc<Token>),
+    ConstantAssignment(Rc<Token>),
 }
 
 impl TokenInside for CompileError {
@@ -45,7 +55,15 @@ impl TokenInside for CompileError {
             | Self::InvalidExportUsage(token, ..)
             | Self::Io(token, ..)
             | Self::ModuleParser(token, ..)
-            | Self::TooManyArgs(token, ..) => Rc::clone(token),
+            | Self::ImportCycle(token, ..)
+            | Self::UnknownNativeModule(token, ..)
+            | Self::TooManyArgs(token, ..)
+            | Self::InvalidPattern(token, ..)
+            | Self::OutsideClass(token, ..)
+            | Self::YieldOutsideFunction(token, ..)
+            | Self::UnknownErrorKind(token, ..)
+            | Self::UnknownLabel(token, ..)
+            | Self::ConstantAssignment(token, ..) => Rc::clone(token),
         }
     }
 }
@@ -73,7 +91,7 @@ impl fmt::Display for CompileError {
                 writeln!(f, "{token}")?;
                 write!(
                     f,
-                    "حيث يمكن أن تكون متلية فقط ب\"n\" أو \"r\" أو \"t\" أو '\"'"
+                    "حيث يمكن أن تكون متلية فقط ب\"n\" أو \"r\" أو \"t\" أو '\"' أو \"\\\" أو \"{{\" أو \"u{{...}}\""
                 )
             }
             Self::DefaultInObject(token) => {
@@ -111,6 +129,10 @@ impl fmt::Display for CompileError {
                 writeln!(f, "لا يمكن الإرجاع من خارج دالة")?;
                 write!(f, "{token}")
             }
+            Self::YieldOutsideFunction(token) => {
+                writeln!(f, "لا يمكن الإنتاج من خارج دالة")?;
+                write!(f, "{token}")
+            }
             Self::TooManyExports(token) => {
                 writeln!(f, "لا يمكن تصدير أكثر من 65535 عنصر")?;
                 write!(f, "{token}")
@@ -123,6 +145,18 @@ impl fmt::Display for CompileError {
                 writeln!(f, "لا يمكن استخدام \"واصل\" خارج حلقة تكرارية")?;
                 write!(f, "{token}")
             }
+            Self::ConstantAssignment(token) => {
+                writeln!(f, "لا يمكن تغيير قيمة الثابت {}", token.lexeme())?;
+                write!(f, "{token}")
+            }
+            Self::UnknownLabel(token) => {
+                writeln!(
+                    f,
+                    "لا توجد حلقة تكرارية باسم {} حول هذه العبارة",
+                    token.lexeme()
+                )?;
+                write!(f, "{token}")
+            }
             Self::InvalidImportUsage(token) => {
                 writeln!(f, "لا يمكن التصدير من داخل الدوال أو المجموعات")?;
                 write!(f, "{token}")
@@ -149,23 +183,92 @@ impl fmt::Display for CompileError {
                 let mut iter = errors.iter();
                 if let Some(err) = iter.next() {
                     write!(f, "{err}")?;
-                    while let Some(err) = iter.next() {
+                    for err in iter {
                         write!(f, "{err}")?
                     }
                 }
                 Ok(())
             }
+            Self::ImportCycle(token, paths) => {
+                writeln!(f, "لا يمكن أن تستورد الوحدات بعضها بشكل دائري")?;
+                writeln!(
+                    f,
+                    "{}",
+                    paths
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(" ← ")
+                )?;
+                write!(f, "{token}")
+            }
+            Self::UnknownNativeModule(token, name) => {
+                writeln!(
+                    f,
+                    "لا توجد وحدة قياسية باسم {name}، الوحدات الموجودة هي: {}",
+                    NATIVE_MODULES.join("، ")
+                )?;
+                write!(f, "{token}")
+            }
             Self::TooManyArgs(token) => {
                 writeln!(f, "لا يمكن استدعاء دالة بأكثر من 255 مدخل")?;
                 write!(f, "{token}")
             }
+            Self::OutsideClass(token) => {
+                match token.typ() {
+                    TokenType::This => writeln!(f, "لا يمكن استخدام \"هذا\" خارج دوال الأصناف")?,
+                    _ => writeln!(
+                        f,
+                        "لا يمكن استخدام \"أصل\" خارج دوال الأصناف التي ترث من أصناف أخرى"
+                    )?,
+                }
+                write!(f, "{token}")
+            }
+            Self::InvalidPattern(token) => {
+                writeln!(
+                    f,
+                    "يمكن فقط استخدام الكلمات والأرقام والنصوص والقيم المنطقية والعدم والقوائم والكائنات في الأنماط"
+                )?;
+                write!(f, "{token}")
+            }
+            Self::UnknownErrorKind(token) => {
+                writeln!(
+                    f,
+                    "لا يوجد نوع خطأ باسم {}، الأنواع الموجودة هي: {}",
+                    token.lexeme(),
+                    ERROR_KINDS.join("، ")
+                )?;
+                write!(f, "{token}")
+            }
         }
     }
 }
 
+/// The property of error values that holds their kind.
+pub const KIND: &str = "النوع";
+
+/// The kinds returned by `RuntimeError::kind`.
+pub const ERROR_KINDS: [&str; 14] = [
+    "نوع",
+    "اسم",
+    "تعريف",
+    "مدخلات",
+    "فهرس",
+    "نطاق",
+    "مستخدم",
+    "توزيع",
+    "خاصية",
+    "ملف",
+    "خطوة",
+    "تعيين",
+    "مكدس",
+    "استدعاء",
+];
+
 #[derive(Debug, Clone)]
 pub enum RuntimeError {
-    Type(Vec<DataType>, DataType, Rc<Token>, Backtrace),
+    /// The expected types and the types of the received values, which are the operands for operators.
+    Type(Vec<DataType>, Vec<DataType>, Rc<Token>, Backtrace),
     Name(String, Rc<Token>, Backtrace),
     AlreadyDefined(String, Rc<Token>, Backtrace),
     InvalidArgc(Arity, usize, Rc<Token>, Backtrace),
@@ -175,6 +278,14 @@ pub enum RuntimeError {
     ListUnpack(usize, usize, Rc<Token>, Backtrace),
     UndefinedKey(String, Rc<Token>, Backtrace),
     Io(Rc<io::Error>, Rc<Token>, Backtrace),
+    ZeroStep(Rc<Token>, Backtrace),
+    Constant(String, Rc<Token>, Backtrace),
+    /// The maximum call depth.
+    StackOverflow(usize, Rc<Token>, Backtrace),
+    /// The type of the called value.
+    Uncallable(DataType, Rc<Token>, Backtrace),
+    /// The number with a fractional part that was given where an integer is expected.
+    NotInteger(f64, Rc<Token>, Backtrace),
 }
 
 impl RuntimeError {
@@ -182,12 +293,17 @@ impl RuntimeError {
         match self {
             Self::Type(expected, received, ..) => {
                 format!(
-                    "توقعت {} ولكن حصلت على {received}",
+                    "توقعت {} ولكن حصلت على {}",
                     expected
                         .iter()
                         .map(|dt| format!("{dt}"))
                         .collect::<Vec<_>>()
-                        .join("أو ")
+                        .join(" أو "),
+                    received
+                        .iter()
+                        .map(|dt| format!("{dt}"))
+                        .collect::<Vec<_>>()
+                        .join(" و")
                 )
             }
             Self::Name(name, ..) => format!("المتغير {name} غير معرّف"),
@@ -206,7 +322,7 @@ impl RuntimeError {
                 buf += format!(" ولكن حصلت على {argc}").as_str();
                 buf
             }
-            Self::InvalidIdx(..) => format!("يجب أن تكون القيمة المفهرس بها عدداً صحيحاً موجباً"),
+            Self::InvalidIdx(..) => "يجب أن تكون القيمة المفهرس بها عدداً صحيحاً موجباً".to_owned(),
             Self::OutOfRange(idx, len, ..) => {
                 format!("لا يمكن الفهرسة ب{idx} في مرتّب حجمه {len}")
             }
@@ -216,9 +332,69 @@ impl RuntimeError {
             }
             Self::UndefinedKey(key, ..) => format!("لا توجد الخاصية {key} في هذا الكائن"),
             Self::Io(err, ..) => format!("{err}"),
+            Self::ZeroStep(..) => "لا يمكن أن تكون خطوة المدى صفراً".to_owned(),
+            Self::Constant(name, ..) => format!("لا يمكن تغيير قيمة الثابت {name}"),
+            Self::StackOverflow(max_depth, ..) => {
+                format!("تجاوزت الاستدعاءات المتداخلة الحد الأقصى وهو {max_depth} استدعاء")
+            }
+            Self::Uncallable(typ, ..) => format!("لا يمكن استدعاء قيمة من نوع {typ}"),
+            Self::NotInteger(number, ..) => {
+                format!("توقعت عدداً صحيحاً ولكن حصلت على {}", Value::Number(*number))
+            }
+        }
+    }
+
+    /// The name scripts use to tell errors apart.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Type(..) => "نوع",
+            Self::Name(..) => "اسم",
+            Self::AlreadyDefined(..) => "تعريف",
+            Self::InvalidArgc(..) => "مدخلات",
+            Self::InvalidIdx(..) => "فهرس",
+            Self::OutOfRange(..) => "نطاق",
+            Self::User(..) => "مستخدم",
+            Self::ListUnpack(..) => "توزيع",
+            Self::UndefinedKey(..) => "خاصية",
+            Self::Io(..) => "ملف",
+            Self::ZeroStep(..) => "خطوة",
+            Self::Constant(..) => "تعيين",
+            Self::StackOverflow(..) => "مكدس",
+            Self::Uncallable(..) => "استدعاء",
+            Self::NotInteger(..) => "نوع",
         }
     }
 
+    /// The properties of the error values that `أمسك` receives, which are its kind, message, line, path, and backtrace.
+    pub fn property(&self, key: &str) -> Option<Value> {
+        let token = self.token();
+        Some(match key {
+            KIND => Value::from(self.kind()),
+            "الرسالة" => Value::from(self.msg()),
+            "السطر" => Value::from(token.line()),
+            "الملف" => match token.path() {
+                Some(path) => Value::from(path.display().to_string()),
+                None => Value::Nil,
+            },
+            "التتبع" => Value::from(
+                self.backtrace()
+                    .frames()
+                    .iter()
+                    .map(|(name, token)| {
+                        Value::from(HashMap::from([
+                            (
+                                "الدالة".to_owned(),
+                                name.clone().map_or(Value::Nil, Value::from),
+                            ),
+                            ("السطر".to_owned(), Value::from(token.line())),
+                        ]))
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            _ => return None,
+        })
+    }
+
     pub fn backtrace(&self) -> &Backtrace {
         match self {
             Self::Type(.., backtrace)
@@ -230,7 +406,12 @@ impl RuntimeError {
             | Self::User(.., backtrace)
             | Self::ListUnpack(.., backtrace)
             | Self::UndefinedKey(.., backtrace)
-            | Self::Io(.., backtrace) => backtrace,
+            | Self::Io(.., backtrace)
+            | Self::ZeroStep(.., backtrace)
+            | Self::Constant(.., backtrace)
+            | Self::StackOverflow(.., backtrace)
+            | Self::Uncallable(.., backtrace)
+            | Self::NotInteger(.., backtrace) => backtrace,
         }
     }
 
@@ -245,7 +426,12 @@ impl RuntimeError {
             | Self::User(.., backtrace)
             | Self::ListUnpack(.., backtrace)
             | Self::UndefinedKey(.., backtrace)
-            | Self::Io(.., backtrace) => backtrace,
+            | Self::Io(.., backtrace)
+            | Self::ZeroStep(.., backtrace)
+            | Self::Constant(.., backtrace)
+            | Self::StackOverflow(.., backtrace)
+            | Self::Uncallable(.., backtrace)
+            | Self::NotInteger(.., backtrace) => backtrace,
         }
     }
 }
@@ -262,7 +448,12 @@ impl TokenInside for RuntimeError {
             | Self::User(.., token, _)
             | Self::ListUnpack(.., token, _)
             | Self::UndefinedKey(.., token, _)
-            | Self::Io(.., token, _) => Rc::clone(token),
+            | Self::Io(.., token, _)
+            | Self::ZeroStep(token, _)
+            | Self::Constant(.., token, _)
+            | Self::StackOverflow(.., token, _)
+            | Self::Uncallable(.., token, _)
+            | Self::NotInteger(.., token, _) => Rc::clone(token),
         }
     }
 }
@@ -281,16 +472,17 @@ impl fmt::Display for RuntimeError {
     }
 }
 
-impl Into<Value> for RuntimeError {
-    fn into(self) -> Value {
-        match self {
-            Self::User(value, ..) => value,
-            err => Value::from(err.msg()),
+impl From<RuntimeError> for Value {
+    /// Thrown values are kept as they are while the built-in errors become error values.
+    fn from(err: RuntimeError) -> Self {
+        match err {
+            RuntimeError::User(value, ..) => value,
+            err => Value::Object(Object::Error(Rc::new(err))),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Backtrace {
     inner: Vec<(Option<String>, Rc<Token>)>,
 }
@@ -299,35 +491,54 @@ impl Backtrace {
     pub fn push(&mut self, name: Option<String>, token: Rc<Token>) {
         self.inner.push((name, token));
     }
-}
 
-impl Default for Backtrace {
-    fn default() -> Self {
-        Self { inner: vec![] }
+    /// name, token: starting from the frame where the error was thrown.
+    pub fn frames(&self) -> &[(Option<String>, Rc<Token>)] {
+        &self.inner
     }
 }
 
+/// The number of frames written from each end of long backtraces.
+const BACKTRACE_EDGE: usize = 10;
+
 impl fmt::Display for Backtrace {
+    /// Writes the consecutive calls from the same place once, and skips the middle of long backtraces (e.g. of stack overflows).
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        macro_rules! write_frame {
-            ($frame:ident) => {{
-                let line = $frame.1.line();
-                write!(
-                    f,
-                    "في {} السطر رقم {line}",
-                    match &$frame.0 {
-                        Some(name) => format!("الدالة {name}"),
-                        None => "دالة غير معروفة".into(),
-                    }
-                )
-            }};
+        // name, token, repetitions
+        let mut frames: Vec<(&Option<String>, &Rc<Token>, usize)> = vec![];
+        for (name, token) in &self.inner {
+            match frames.last_mut() {
+                Some((last_name, last_token, repetitions))
+                    if *last_name == name && Rc::ptr_eq(last_token, token) =>
+                {
+                    *repetitions += 1
+                }
+                _ => frames.push((name, token, 1)),
+            }
         }
-        let mut iter = self.inner.iter();
-        if let Some(frame) = iter.next() {
-            write_frame!(frame)?;
-            while let Some(frame) = iter.next() {
-                write!(f, "\n")?;
-                write_frame!(frame)?
+        let skipped = frames.len().saturating_sub(BACKTRACE_EDGE * 2);
+        for (idx, (name, token, repetitions)) in frames.iter().enumerate() {
+            if skipped > 0 && idx >= BACKTRACE_EDGE && idx < frames.len() - BACKTRACE_EDGE {
+                if idx == BACKTRACE_EDGE {
+                    writeln!(f)?;
+                    write!(f, "... ({skipped} أخرى)")?;
+                }
+                continue;
+            }
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "في {} السطر رقم {}",
+                match name {
+                    Some(name) => format!("الدالة {name}"),
+                    None => "دالة غير معروفة".into(),
+                },
+                token.line()
+            )?;
+            if *repetitions > 1 {
+                write!(f, " (تكرر {repetitions} مرة)")?;
             }
         }
         Ok(())
@@ -1,38 +1,62 @@
+#![allow(
+    clippy::result_unit_err,
+    clippy::type_complexity,
+    clippy::too_many_arguments
+)]
+
 pub mod chunk;
 pub mod error;
 
 use chunk::value::{self, Arity, ArityType, Value};
 use chunk::{Chunk, OpCode};
-use error::CompileError;
+use error::{CompileError, ERROR_KINDS, KIND};
 use lexer::{token::*, Lexer};
 use parser::ast::{Expr, Literal, Stml};
 use parser::Parser;
-use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 use std::{cell::RefCell, convert::From, fs, rc::Rc};
 
 use OpCode::*;
 
+/// The modules implemented by the VM, which are imported by prefixing their names with `@`.
+pub const NATIVE_MODULES: [&str; 2] = ["رياضيات", "ذاكرة"];
+
+/// The name of the hidden local that holds the receiver of a method.
+const THIS: &str = "هذا";
+/// The name of the hidden local that holds the parent of a class.
+const SUPER: &str = "أصل";
+
 #[derive(Debug, Clone)]
 struct Local {
     token: Rc<Token>,
+    /// The lexeme of `token` except for the hidden locals like `هذا` and `أصل`.
+    name: String,
     depth: usize,
     captured: bool,
     exported: bool,
+    constant: bool,
 }
 
 impl Local {
-    fn new(token: Rc<Token>, depth: usize) -> Self {
+    fn new(token: Rc<Token>, name: String, depth: usize) -> Self {
         Self {
             token,
+            name,
             depth,
             captured: false,
             exported: false,
+            constant: false,
         }
     }
 
     fn export(&mut self) {
         self.exported = true;
     }
+
+    fn make_constant(&mut self) {
+        self.constant = true;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -59,11 +83,11 @@ impl Locals {
     }
 
     /// Fails when `self.inner` is larger than 256.
-    fn push(&mut self, token: Rc<Token>) -> Result<(), ()> {
+    fn push(&mut self, token: Rc<Token>, name: String) -> Result<(), ()> {
         if self.inner.capacity() == self.inner.len() {
             Err(())
         } else {
-            self.inner.push(Local::new(token, self.depth));
+            self.inner.push(Local::new(token, name, self.depth));
             Ok(())
         }
     }
@@ -87,7 +111,7 @@ impl Locals {
                 return Ok(upvalue_idx);
             }
         }
-        if self.upvalues.capacity() == self.inner.len() {
+        if self.upvalues.capacity() == self.upvalues.len() {
             Err(())
         } else {
             let len = self.upvalues.len();
@@ -96,41 +120,65 @@ impl Locals {
         }
     }
 
-    /// `token` must be of type `Identifier`.
-    fn resolve_upvalue(&mut self, token: Rc<Token>) -> Result<Option<usize>, ()> {
+    fn resolve_upvalue(&mut self, name: &str) -> Result<Option<usize>, ()> {
         match self.enclosing.clone() {
             Some(enclosing) => {
                 let mut enclosing = enclosing.borrow_mut();
-                if let Some(idx) = enclosing.resolve_local(Rc::clone(&token)) {
+                if let Some(idx) = enclosing.resolve_local(name) {
+                    // So that it gets closed instead of popped when its scope ends.
+                    enclosing.inner[idx].captured = true;
                     Ok(Some(self.add_upvalue(true, idx)?))
                 } else {
-                    enclosing.resolve_upvalue(token)
+                    match enclosing.resolve_upvalue(name)? {
+                        Some(idx) => Ok(Some(self.add_upvalue(false, idx)?)),
+                        None => Ok(None),
+                    }
                 }
             }
             None => Ok(None),
         }
     }
 
-    /// `token` must be of type `Identifier`.
-    fn resolve_local(&self, token: Rc<Token>) -> Option<usize> {
+    fn resolve_local(&self, name: &str) -> Option<usize> {
         for (
             idx,
             Local {
-                token: local_token, ..
+                name: local_name, ..
             },
         ) in self.inner.iter().enumerate().rev()
         {
-            if token.lexeme() == local_token.lexeme() {
+            if name == local_name {
                 return Some(idx);
             }
         }
         None
     }
 
+    /// Whether `name` resolves to a constant local of this function or of an enclosing one.
+    fn is_constant(&self, name: &str) -> bool {
+        match self.resolve_local(name) {
+            Some(idx) => self.inner[idx].constant,
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow().is_constant(name),
+                None => false,
+            },
+        }
+    }
+
     fn start_scope(&mut self) {
         self.depth += 1;
     }
 
+    /// Same as `end_scope` but for all the scopes deeper than `depth` and without leaving them or popping their locals.
+    fn captures_after(&self, depth: usize) -> Vec<bool> {
+        self.inner
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > depth)
+            .map(|local| local.captured)
+            .collect()
+    }
+
     /// The returned vector represents whether the locals popped were captured or not.
     fn end_scope(&mut self) -> Vec<bool> {
         let mut tmp = vec![];
@@ -146,6 +194,46 @@ impl Locals {
     }
 }
 
+#[derive(Debug, Clone)]
+struct Loop {
+    label: Option<String>,
+    start: usize,
+    /// The scope depth outside the loop.
+    depth: usize,
+    /// The number of handlers outside the loop.
+    handlers: usize,
+    /// The number of tmps kept by the statements outside the loop's body, which includes the iterator of `لكل`.
+    tmps: usize,
+    /// The ips of the jumps of its breaks.
+    breaks: Vec<usize>,
+}
+
+/// The modules of a script, shared between the compilers of the script and the modules it imports.
+#[derive(Debug, Default)]
+struct Modules {
+    /// The compiled modules along with the names of their constant exports, by their canonical paths.
+    compiled: HashMap<PathBuf, (Rc<value::Function>, Vec<String>)>,
+    /// The files being compiled, each importing the next, as their canonical paths and the paths they were imported by.
+    importing: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Drops the `.` components of `path` and resolves its `..` ones lexically, so that the modules of an import cycle read the same however their imports were spelled.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(normalized.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CompilerType {
     Script,
@@ -164,10 +252,15 @@ pub struct Compiler<'a> {
     token: Rc<Token>,
     chunk: Chunk,
     locals: Rc<RefCell<Locals>>,
-    /// A vector containing jumps ips.
-    breaks: Vec<usize>,
-    /// A vector containing enclosing loops starts.
-    loops: Vec<usize>,
+    /// The enclosing loops.
+    loops: Vec<Loop>,
+    /// The enclosing errors handlers, where the ones of `أخيراً` blocks hold their bodies.
+    handlers: Vec<Option<Stml>>,
+    /// The number of values kept on tmps by the enclosing statements while their bodies run (e.g. the iterators of `لكل` and the subjects of `طابق`).
+    tmps: usize,
+    /// Whether a `أنتج` was compiled, which makes the function a generator.
+    generator: bool,
+    modules: Rc<RefCell<Modules>>,
     errors: Vec<CompileError>,
 }
 
@@ -179,13 +272,28 @@ impl<'a> Compiler<'a> {
             token,
             chunk: Chunk::new(),
             locals: Rc::new(RefCell::new(Locals::new(None))),
-            breaks: vec![],
             loops: vec![],
+            handlers: vec![],
+            tmps: 0,
+            generator: false,
+            modules: Rc::new(RefCell::new(Modules::default())),
             errors: vec![],
         }
     }
 
-    fn new_function(token: Rc<Token>, body: &'a Stml, enclosing: Rc<RefCell<Locals>>) -> Self {
+    fn new_module(ast: &'a Vec<Stml>, token: Rc<Token>, modules: Rc<RefCell<Modules>>) -> Self {
+        Self {
+            modules,
+            ..Self::new(CompilerType::Module, ast, token)
+        }
+    }
+
+    fn new_function(
+        token: Rc<Token>,
+        body: &'a Stml,
+        enclosing: Rc<RefCell<Locals>>,
+        modules: Rc<RefCell<Modules>>,
+    ) -> Self {
         let ast = match body {
             Stml::Block(_, stmls) => stmls,
             _ => unreachable!(),
@@ -196,8 +304,11 @@ impl<'a> Compiler<'a> {
             token,
             chunk: Chunk::new(),
             locals: Rc::new(RefCell::new(Locals::new(Some(enclosing)))),
-            breaks: vec![],
             loops: vec![],
+            handlers: vec![],
+            tmps: 0,
+            generator: false,
+            modules,
             errors: vec![],
         }
     }
@@ -210,10 +321,6 @@ impl<'a> Compiler<'a> {
         self.typ == CompilerType::Script && self.locals.borrow().depth == 0
     }
 
-    fn in_loop(&self) -> bool {
-        !self.loops.is_empty()
-    }
-
     fn ip(&self) -> usize {
         self.chunk.len()
     }
@@ -249,7 +356,7 @@ impl<'a> Compiler<'a> {
     }
 
     fn write_string_of_ident(&mut self, token: Rc<Token>) -> Result<(), ()> {
-        self.write_const(Rc::clone(&token), Value::from(token.lexeme().clone()))
+        self.write_const(Rc::clone(&token), Value::from(token.lexeme()))
     }
 
     fn write_build(&mut self, op_code: OpCode, token: Rc<Token>, size: usize) -> Result<(), ()> {
@@ -270,6 +377,17 @@ impl<'a> Compiler<'a> {
             .map_err(|_| self.err(CompileError::HugeJump(token)))
     }
 
+    fn write_list_pattern(
+        &mut self,
+        op_code: OpCode,
+        token: Rc<Token>,
+        size: usize,
+    ) -> Result<(), ()> {
+        self.chunk
+            .write_list_pattern(op_code, Rc::clone(&token), size)
+            .map_err(|_| self.err(CompileError::HugeSize(token)))
+    }
+
     fn write_list_unpack(&mut self, token: Rc<Token>, to: usize) -> Result<(), ()> {
         self.chunk
             .write_list_unpack(Rc::clone(&token), to)
@@ -285,7 +403,7 @@ impl<'a> Compiler<'a> {
     fn write_closure(
         &mut self,
         token: Rc<Token>,
-        function: value::Function,
+        function: Rc<value::Function>,
         upvalues: Vec<(bool, usize)>,
     ) -> Result<(), ()> {
         self.chunk
@@ -299,14 +417,9 @@ impl<'a> Compiler<'a> {
             .map_err(|_| self.err(CompileError::TooManyArgs(token)))
     }
 
-    #[allow(unused_must_use)]
-    fn write_call_unchecked(&mut self, token: Rc<Token>, argc: usize) {
-        self.chunk.write_call(token, argc);
-    }
-
-    fn push(&mut self, token: Rc<Token>) -> Result<(), ()> {
+    fn push(&mut self, token: Rc<Token>, name: String) -> Result<(), ()> {
         let mut locals = self.locals.borrow_mut();
-        let res = locals.push(Rc::clone(&token));
+        let res = locals.push(Rc::clone(&token), name);
         drop(locals);
         match res {
             Ok(_) => Ok(()),
@@ -317,29 +430,42 @@ impl<'a> Compiler<'a> {
         }
     }
 
-    fn quoted_string(&mut self, token: Rc<Token>) -> Result<String, ()> {
+    /// Parses a quoted string or one of the segments of an interpolated one, processing its escape sequences.
+    fn quoted_string(&mut self, token: Rc<Token>, triple: bool) -> Result<String, ()> {
+        let quote = if triple { "\"\"\"" } else { "\"" };
+        let lexeme = token.lexeme();
+        let lexeme = lexeme
+            .strip_prefix('}')
+            .or_else(|| lexeme.strip_prefix(quote))
+            .unwrap();
+        let lexeme = match token.typ() {
+            TokenType::Interpolation => lexeme.strip_suffix('{'),
+            _ => lexeme.strip_suffix(quote),
+        }
+        .unwrap();
         let mut content = String::new();
-        let mut iter = token.lexeme().chars().skip(1);
+        let mut iter = lexeme.chars();
         while let Some(ch) = iter.next() {
             if ch == '\\' {
-                if let Some(ch) = iter.next() {
-                    match ch {
-                        'n' => content.push('\n'),
-                        'r' => content.push('\r'),
-                        't' => content.push('\t'),
-                        '\\' => content.push('\\'),
-                        '"' => content.push('"'),
-                        _ => {
+                match iter.next() {
+                    Some('n') => content.push('\n'),
+                    Some('r') => content.push('\r'),
+                    Some('t') => content.push('\t'),
+                    Some('\\') => content.push('\\'),
+                    Some('"') => content.push('"'),
+                    Some('{') => content.push('{'),
+                    Some('u') => match Self::unicode_escape(&mut iter) {
+                        Some(ch) => content.push(ch),
+                        None => {
                             self.err(CompileError::BackSlashMisuse(token));
                             return Err(());
                         }
+                    },
+                    _ => {
+                        self.err(CompileError::BackSlashMisuse(token));
+                        return Err(());
                     }
-                } else {
-                    self.err(CompileError::BackSlashMisuse(token));
-                    return Err(());
                 }
-            } else if ch == '"' {
-                break;
             } else {
                 content.push(ch);
             }
@@ -347,12 +473,57 @@ impl<'a> Compiler<'a> {
         Ok(content)
     }
 
-    /// Parses quoted strings and unquoted ones.
+    /// Parses the `{...}` part of a `\u{...}` escape sequence, which must contain one to six hex digits making up a valid character.
+    fn unicode_escape(iter: &mut std::str::Chars) -> Option<char> {
+        if iter.next() != Some('{') {
+            return None;
+        }
+        let mut digits = String::new();
+        loop {
+            match iter.next()? {
+                '}' => break,
+                ch if ch.is_ascii_hexdigit() && digits.len() < 6 => digits.push(ch),
+                _ => return None,
+            }
+        }
+        char::from_u32(u32::from_str_radix(&digits, 16).ok()?)
+    }
+
+    /// Parses quoted strings, raw ones, and unquoted ones.
     fn string(&mut self, token: Rc<Token>) -> Result<String, ()> {
-        if token.lexeme().starts_with("\"") {
-            self.quoted_string(token)
+        let lexeme = token.lexeme();
+        if lexeme.starts_with("\"\"\"") {
+            self.quoted_string(token, true)
+        } else if lexeme.starts_with('"') {
+            self.quoted_string(token, false)
+        } else if let Some(raw) = lexeme.strip_prefix('`') {
+            Ok(raw.strip_suffix('`').unwrap().to_owned())
         } else {
-            Ok(token.lexeme().to_owned())
+            Ok(lexeme.to_owned())
+        }
+    }
+
+    /// Parses number literals written with either ASCII or Arabic-Indic digits, in decimal, hexadecimal (`0x`), octal (`0o`), or binary (`0b`), with optional `_` separators.
+    fn number(&mut self, token: Rc<Token>) -> Result<f64, ()> {
+        let normalized = token
+            .lexeme()
+            .chars()
+            .filter(|ch| *ch != '_')
+            .map(|ch| match ch {
+                '\u{0660}'..='\u{0669}' => {
+                    char::from_digit(ch as u32 - '\u{0660}' as u32, 10).unwrap()
+                }
+                '\u{066b}' => '.',
+                ch => ch,
+            })
+            .collect::<String>();
+        let mut chars = normalized.chars().skip(1);
+        match chars.next().and_then(lexer::radix) {
+            // Folding into a float rather than parsing an integer keeps huge literals from overflowing.
+            Some(radix) => Ok(chars.fold(0.0, |acc, ch| {
+                acc * radix as f64 + lexer::to_digit(ch, radix).unwrap() as f64
+            })),
+            None => Ok(normalized.parse().unwrap()),
         }
     }
 
@@ -365,6 +536,9 @@ impl<'a> Compiler<'a> {
             TokenType::Bang => {
                 self.chunk.write_instr_no_operands(NOT, op);
             }
+            TokenType::Tilde => {
+                self.chunk.write_instr_no_operands(BIT_NOT, op);
+            }
             _ => unreachable!(),
         }
         Ok(())
@@ -418,6 +592,12 @@ impl<'a> Compiler<'a> {
                 self.settle_jump(truthy_lhs)?;
                 return Ok(());
             }
+            TokenType::DQuestionMark => {
+                let not_nil_lhs = self.chunk.write_jump(JUMP_IF_NOT_NIL_OR_POP, op);
+                self.expr(rhs)?;
+                self.settle_jump(not_nil_lhs)?;
+                return Ok(());
+            }
             _ => {}
         }
         self.expr(rhs)?;
@@ -427,12 +607,20 @@ impl<'a> Compiler<'a> {
             TokenType::Star => self.chunk.write_instr_no_operands(MUL, op),
             TokenType::Slash => self.chunk.write_instr_no_operands(DIV, op),
             TokenType::Percent => self.chunk.write_instr_no_operands(REM, op),
+            TokenType::DStar => self.chunk.write_instr_no_operands(POW, op),
+            TokenType::BackSlash => self.chunk.write_instr_no_operands(FLOOR_DIV, op),
+            TokenType::Ampersand => self.chunk.write_instr_no_operands(BIT_AND, op),
+            TokenType::Pipe => self.chunk.write_instr_no_operands(BIT_OR, op),
+            TokenType::Caret => self.chunk.write_instr_no_operands(BIT_XOR, op),
+            TokenType::DLess => self.chunk.write_instr_no_operands(SHL, op),
+            TokenType::DGreater => self.chunk.write_instr_no_operands(SHR, op),
             TokenType::DEqual => self.chunk.write_instr_no_operands(EQ, op),
             TokenType::BangEqual => self.chunk.write_instr_no_operands(NOT_EQ, op),
             TokenType::Greater => self.chunk.write_instr_no_operands(GREATER, op),
             TokenType::GreaterEqual => self.chunk.write_instr_no_operands(GREATER_EQ, op),
             TokenType::Less => self.chunk.write_instr_no_operands(LESS, op),
             TokenType::LessEqual => self.chunk.write_instr_no_operands(LESS_EQ, op),
+            TokenType::In => self.chunk.write_instr_no_operands(CONTAINS, op),
             _ => unreachable!(),
         }
         Ok(())
@@ -441,21 +629,27 @@ impl<'a> Compiler<'a> {
     fn lambda(
         &mut self,
         token: &Rc<Token>,
-        required: &Vec<Expr>,
-        optional: &Vec<(Expr, Expr)>,
+        required: &[Expr],
+        optional: &[(Expr, Expr)],
         variadic: &Option<(Rc<Token>, Box<Expr>)>,
         body: &Stml,
     ) -> Result<(), ()> {
-        self.function(body, required, optional, variadic, None, Rc::clone(token))
+        self.function(
+            body,
+            required,
+            optional,
+            variadic,
+            None,
+            Rc::clone(token),
+            false,
+        )
     }
 
     fn literal(&mut self, literal: &Literal) -> Result<(), ()> {
         match literal {
             Literal::Number(token) => {
-                self.write_const(
-                    Rc::clone(token),
-                    Value::Number(token.lexeme().clone().parse().unwrap()),
-                )?;
+                let value = Value::Number(self.number(Rc::clone(token))?);
+                self.write_const(Rc::clone(token), value)?;
             }
             Literal::Bool(token) => {
                 self.bool(
@@ -475,18 +669,27 @@ impl<'a> Compiler<'a> {
                 self.nil(Rc::clone(token));
             }
             // TODO report HugeSize with better tokens
-            Literal::List(token, exprs) => {
-                let mut size = 0;
-                for expr in exprs {
-                    self.expr(expr)?;
-                    size += 1;
-                }
-                self.write_build(BUILD_LIST, Rc::clone(token), size)?
-            }
+            Literal::List(token, exprs) => self.list(Rc::clone(token), exprs)?,
             Literal::Object(token, props) => {
                 let mut size = 0;
+                let mut built = false;
                 for (key, value, default) in props {
-                    self.write_const(Rc::clone(key), Value::from(key.lexeme().clone()))?;
+                    if let Some(Expr::Spread(op, expr)) = value {
+                        if !built || size > 0 {
+                            self.write_build(BUILD_HASH_MAP, Rc::clone(token), size)?;
+                            if built {
+                                self.chunk
+                                    .write_instr_no_operands(MERGE_HASH_MAP, Rc::clone(op));
+                            }
+                        }
+                        self.expr(expr)?;
+                        self.chunk
+                            .write_instr_no_operands(MERGE_HASH_MAP, Rc::clone(op));
+                        size = 0;
+                        built = true;
+                        continue;
+                    }
+                    self.write_const(Rc::clone(key), Value::from(key.lexeme()))?;
                     match value {
                         Some(lhs) => match default {
                             Some((op, rhs)) => self.binary(lhs, Rc::clone(op), rhs)?,
@@ -499,7 +702,13 @@ impl<'a> Compiler<'a> {
                     }
                     size += 1;
                 }
-                self.write_build(BUILD_HASH_MAP, Rc::clone(token), size)?
+                if !built || size > 0 {
+                    self.write_build(BUILD_HASH_MAP, Rc::clone(token), size)?;
+                    if built {
+                        self.chunk
+                            .write_instr_no_operands(MERGE_HASH_MAP, Rc::clone(token));
+                    }
+                }
             }
             Literal::Lambda(token, required, optional, variadic, body) => {
                 self.lambda(token, required, optional, variadic, body)?
@@ -508,12 +717,38 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
+    /// `token` must be of type `Identifier`.
     fn resolve_local(&self, token: Rc<Token>) -> Option<usize> {
-        self.locals.borrow().resolve_local(token)
+        self.locals.borrow().resolve_local(token.lexeme())
     }
 
+    /// `token` must be of type `Identifier`.
     fn resolve_upvalue(&self, token: Rc<Token>) -> Result<Option<usize>, ()> {
-        self.locals.borrow_mut().resolve_upvalue(token)
+        self.locals.borrow_mut().resolve_upvalue(token.lexeme())
+    }
+
+    /// Gets a hidden local (i.e., `هذا` or `أصل`) which can only be defined by `define_hidden`.
+    fn get_hidden(&mut self, token: Rc<Token>, name: &str) -> Result<(), ()> {
+        let local = self.locals.borrow().resolve_local(name);
+        if let Some(idx) = local {
+            self.write_instr_idx(GET_LOCAL, token, idx);
+            return Ok(());
+        }
+        let upvalue = self.locals.borrow_mut().resolve_upvalue(name);
+        match upvalue {
+            Ok(Some(idx)) => {
+                self.write_instr_idx(GET_UPVALUE, token, idx);
+                Ok(())
+            }
+            Ok(None) => {
+                self.err(CompileError::OutsideClass(token));
+                Err(())
+            }
+            Err(_) => {
+                self.err(CompileError::TooManyUpvalues(token));
+                Err(())
+            }
+        }
     }
 
     /// `expr` must be a variable or member expressions, otherwise it panics.
@@ -523,8 +758,8 @@ impl<'a> Compiler<'a> {
                 if let Some(idx) = self.resolve_local(Rc::clone(token)) {
                     self.write_instr_idx(GET_LOCAL, Rc::clone(token), idx);
                 } else {
-                    match self.resolve_upvalue(Rc::clone(token)) {
-                        Ok(idx) => match idx {
+                    if let Ok(idx) = self.resolve_upvalue(Rc::clone(token)) {
+                        match idx {
                             Some(idx) => {
                                 self.write_instr_idx(GET_UPVALUE, Rc::clone(token), idx);
                             }
@@ -532,26 +767,97 @@ impl<'a> Compiler<'a> {
                                 self.write_instr_const(
                                     (GET_GLOBAL8, GET_GLOBAL16),
                                     Rc::clone(token),
-                                    Value::from(token.lexeme().clone()),
+                                    Value::from(token.lexeme()),
                                 )?;
                             }
-                        },
-                        Err(_) => {}
+                        }
                     }
                 }
             }
+            Expr::Member(..) => self.chain(expr)?,
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Compiles a chain of member accesses and calls, an optional access on nil skips the rest of it so that it results in nil.
+    fn chain(&mut self, expr: &Expr) -> Result<(), ()> {
+        let mut nil_jumps = vec![];
+        self.link(expr, &mut nil_jumps)?;
+        for jump in nil_jumps {
+            self.settle_jump(jump)?;
+        }
+        Ok(())
+    }
+
+    /// Compiles a link of a chain, the jumps of its optional accesses are added to `nil_jumps` to be settled at the end of the chain.
+    fn link(&mut self, expr: &Expr, nil_jumps: &mut Vec<usize>) -> Result<(), ()> {
+        match expr {
             Expr::Member(expr, op, key) => {
-                self.expr(expr)?;
+                self.link(expr, nil_jumps)?;
+                let op_code = match op.typ() {
+                    TokenType::QuestionPeriod => {
+                        nil_jumps.push(self.chunk.write_jump(JUMP_IF_NIL, Rc::clone(op)));
+                        GET_OPTIONAL
+                    }
+                    _ => GET,
+                };
                 self.expr(key)?;
-                self.chunk.write_instr_no_operands(GET, Rc::clone(op));
+                self.chunk.write_instr_no_operands(op_code, Rc::clone(op));
+                Ok(())
+            }
+            Expr::Call(callee, op, exprs) => {
+                self.link(callee, nil_jumps)?;
+                self.call(Rc::clone(op), exprs)
+            }
+            _ => self.expr(expr),
+        }
+    }
+
+    /// Builds a list out of `exprs`, splicing the spread ones into it.
+    fn list(&mut self, token: Rc<Token>, exprs: &[Expr]) -> Result<(), ()> {
+        let mut size = 0;
+        let mut built = false;
+        for expr in exprs {
+            match expr {
+                Expr::Spread(op, expr) => {
+                    if !built || size > 0 {
+                        self.write_build(BUILD_LIST, Rc::clone(&token), size)?;
+                        if built {
+                            self.chunk
+                                .write_instr_no_operands(EXTEND_LIST, Rc::clone(op));
+                        }
+                    }
+                    self.expr(expr)?;
+                    self.chunk
+                        .write_instr_no_operands(EXTEND_LIST, Rc::clone(op));
+                    size = 0;
+                    built = true;
+                }
+                _ => {
+                    self.expr(expr)?;
+                    size += 1;
+                }
+            }
+        }
+        if !built || size > 0 {
+            self.write_build(BUILD_LIST, Rc::clone(&token), size)?;
+            if built {
+                self.chunk
+                    .write_instr_no_operands(EXTEND_LIST, Rc::clone(&token));
             }
-            _ => unreachable!(),
         }
         Ok(())
     }
 
-    fn call(&mut self, callee: &Expr, op: Rc<Token>, exprs: &Vec<Expr>) -> Result<(), ()> {
-        self.expr(callee)?;
+    /// Calls TOT with `exprs`.
+    fn call(&mut self, op: Rc<Token>, exprs: &[Expr]) -> Result<(), ()> {
+        if exprs.iter().any(|expr| matches!(expr, Expr::Spread(..))) {
+            // The arguments count is only known at runtime, so they are collected into a list.
+            self.list(Rc::clone(&op), exprs)?;
+            self.chunk.write_instr_no_operands(CALL_LIST, op);
+            return Ok(());
+        }
         for arg in exprs {
             self.expr(arg)?
         }
@@ -561,20 +867,95 @@ impl<'a> Compiler<'a> {
 
     fn expr(&mut self, expr: &Expr) -> Result<(), ()> {
         match expr {
-            Expr::Variable(..) | Expr::Member(..) => self.get(expr),
+            Expr::Variable(..) => self.get(expr),
+            Expr::Member(..) | Expr::Call(..) => self.chain(expr),
+            Expr::This(token) => self.get_hidden(Rc::clone(token), THIS),
+            Expr::Super(token, key) => {
+                self.get_hidden(Rc::clone(token), THIS)?;
+                self.get_hidden(Rc::clone(token), SUPER)?;
+                self.write_string_of_ident(Rc::clone(key))?;
+                self.chunk
+                    .write_instr_no_operands(GET_SUPER, Rc::clone(key));
+                Ok(())
+            }
             Expr::Literal(literal) => self.literal(literal),
             Expr::Unary(op, expr) => self.unary(Rc::clone(op), expr),
             Expr::Binary(lhs, op, rhs) => self.binary(lhs, Rc::clone(op), rhs),
-            Expr::Call(callee, op, exprs) => self.call(callee, Rc::clone(op), exprs),
+            Expr::Range(start, op, end, step) => self.range(start, Rc::clone(op), end, step),
+            Expr::Ternary(condition, op, then, else_) => {
+                self.ternary(condition, Rc::clone(op), then, else_)
+            }
+            Expr::Interpolation(strings, exprs) => self.interpolation(strings, exprs),
+            // Lists, objects, and calls handle their spread elements.
+            Expr::Spread(..) => unreachable!(),
+        }
+    }
+
+    fn range(
+        &mut self,
+        start: &Expr,
+        op: Rc<Token>,
+        end: &Expr,
+        step: &Option<Box<Expr>>,
+    ) -> Result<(), ()> {
+        self.expr(start)?;
+        self.expr(end)?;
+        match step {
+            Some(step) => self.expr(step)?,
+            None => self.write_const(Rc::clone(&op), Value::Number(1.0))?,
+        }
+        self.chunk.write_instr_no_operands(BUILD_RANGE, op);
+        Ok(())
+    }
+
+    fn ternary(
+        &mut self,
+        condition: &Expr,
+        op: Rc<Token>,
+        then: &Expr,
+        else_: &Expr,
+    ) -> Result<(), ()> {
+        self.expr(condition)?;
+        let falsy_condition = self.chunk.write_jump(POP_JUMP_IF_FALSY, Rc::clone(&op));
+        self.expr(then)?;
+        let end = self.chunk.write_jump(JUMP, op);
+        self.settle_jump(falsy_condition)?;
+        self.expr(else_)?;
+        self.settle_jump(end)
+    }
+
+    fn interpolation(&mut self, strings: &[Rc<Token>], exprs: &[Expr]) -> Result<(), ()> {
+        let triple = strings[0].lexeme().starts_with("\"\"\"");
+        let mut size = 0;
+        for (idx, string) in strings.iter().enumerate() {
+            let content = self.quoted_string(Rc::clone(string), triple)?;
+            if !content.is_empty() {
+                self.write_const(Rc::clone(string), Value::from(content))?;
+                size += 1;
+            }
+            if let Some(expr) = exprs.get(idx) {
+                self.expr(expr)?;
+                size += 1;
+            }
         }
+        self.write_build(BUILD_STRING, Rc::clone(&strings[0]), size)
     }
 
     fn define(&mut self, token: Rc<Token>) -> Result<(), ()> {
+        self.declare(token, false)
+    }
+
+    /// Same as `define` but marks the variable as unsettable when `constant` is set.
+    fn declare(&mut self, token: Rc<Token>, constant: bool) -> Result<(), ()> {
         if self.in_global() {
             self.write_instr_const(
-                (DEF_GLOBAL8, DEF_GLOBAL16),
+                if constant {
+                    (DEF_CONST_GLOBAL8, DEF_CONST_GLOBAL16)
+                } else {
+                    (DEF_GLOBAL8, DEF_GLOBAL16)
+                },
                 Rc::clone(&token),
-                Value::from(token.lexeme().clone()),
+                Value::from(token.lexeme()),
             )?
         } else {
             if token.lexeme() != "_" {
@@ -586,22 +967,32 @@ impl<'a> Compiler<'a> {
                 }
             }
 
-            self.push(Rc::clone(&token))?;
+            self.push(Rc::clone(&token), token.lexeme().to_owned())?;
+            if constant {
+                self.locals.borrow_mut().last_mut().make_constant();
+            }
             self.chunk.write_instr_no_operands(DEF_LOCAL, token)
         }
         Ok(())
     }
 
+    /// Defines a local that can't be referenced by an identifier since its name is a keyword.
+    fn define_hidden(&mut self, token: Rc<Token>, name: &str) -> Result<(), ()> {
+        self.push(Rc::clone(&token), name.to_owned())?;
+        self.chunk.write_instr_no_operands(DEF_LOCAL, token);
+        Ok(())
+    }
+
     fn can_export(&self) -> bool {
         self.typ != CompilerType::Function && self.locals.borrow().depth == 0
     }
 
-    fn export(&mut self, token: Rc<Token>) -> Result<(), ()> {
+    fn export(&mut self, token: Rc<Token>, constant: bool) -> Result<(), ()> {
         if !self.can_export() {
             return Err(());
         }
         if !self.in_global() {
-            self.define(Rc::clone(&token))?;
+            self.declare(Rc::clone(&token), constant)?;
             self.locals.borrow_mut().last_mut().export();
         }
         Ok(())
@@ -630,6 +1021,10 @@ impl<'a> Compiler<'a> {
     fn set(&mut self, expr: &Expr, pop: bool) -> Result<(), ()> {
         match expr {
             Expr::Variable(token) => {
+                if self.locals.borrow().is_constant(token.lexeme()) {
+                    self.err(CompileError::ConstantAssignment(Rc::clone(token)));
+                    return Err(());
+                }
                 if let Some(idx) = self.resolve_local(Rc::clone(token)) {
                     self.chunk
                         .write_instr_idx(SET_LOCAL, Rc::clone(token), idx)?
@@ -640,7 +1035,7 @@ impl<'a> Compiler<'a> {
                     self.write_instr_const(
                         (SET_GLOBAL8, SET_GLOBAL16),
                         Rc::clone(token),
-                        Value::from(token.lexeme().clone()),
+                        Value::from(token.lexeme()),
                     )?
                 }
             }
@@ -685,13 +1080,13 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
-    fn definable(&mut self, definable: &Expr, export: bool) -> Result<(), ()> {
+    fn definable(&mut self, definable: &Expr, export: bool, constant: bool) -> Result<(), ()> {
         macro_rules! oper {
             ($token:ident) => {
                 if export {
-                    self.export(Rc::clone($token))?
+                    self.export(Rc::clone($token), constant)?
                 } else {
-                    self.define(Rc::clone($token))?
+                    self.declare(Rc::clone($token), constant)?
                 }
             };
         }
@@ -701,7 +1096,7 @@ impl<'a> Compiler<'a> {
             Expr::Literal(Literal::List(token, exprs)) => {
                 self.write_list_unpack(Rc::clone(token), exprs.len())?;
                 for definable in exprs.iter().rev() {
-                    self.definable(definable, export)?
+                    self.definable(definable, export, constant)?
                 }
             }
             Expr::Literal(Literal::Object(token, props)) => {
@@ -710,7 +1105,7 @@ impl<'a> Compiler<'a> {
                 // 2. Destructuring
                 for (key, value, _) in props {
                     match value {
-                        Some(expr) => self.definable(expr, export)?,
+                        Some(expr) => self.definable(expr, export, constant)?,
                         None => {
                             oper!(key)
                         }
@@ -736,7 +1131,11 @@ impl<'a> Compiler<'a> {
                 Some(expr) => self.expr(expr)?,
                 None => self.nil(Rc::clone(&token)),
             }
-            self.definable(definable, export_token.is_some())?
+            self.definable(
+                definable,
+                export_token.is_some(),
+                token.typ() == TokenType::Const,
+            )?
         }
         Ok(())
     }
@@ -754,12 +1153,23 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    /// Pops the locals of the scopes deeper than `depth` while staying in them, for jumping out of them.
+    fn unwind_scopes(&mut self, token: Rc<Token>, depth: usize) {
+        let captures = self.locals.borrow().captures_after(depth);
+        for captured in captures {
+            self.chunk.write_instr_no_operands(
+                if captured { CLOSE_UPVALUE } else { POP_LOCAL },
+                Rc::clone(&token),
+            )
+        }
+    }
+
     fn if_stml(
         &mut self,
         token: &Rc<Token>,
         condition: &Expr,
-        body: &Box<Stml>,
-        elseifs: &Vec<(Rc<Token>, Expr, Stml)>,
+        body: &Stml,
+        elseifs: &[(Rc<Token>, Expr, Stml)],
         else_: &Option<(Rc<Token>, Box<Stml>)>,
     ) -> Result<(), ()> {
         self.expr(condition)?;
@@ -774,11 +1184,8 @@ impl<'a> Compiler<'a> {
             end.push(self.chunk.write_jump(JUMP, Rc::clone(token)));
             self.settle_jump(falsy_condition)?;
         }
-        match else_ {
-            Some((_, body)) => {
-                self.stml(body)?;
-            }
-            None => {}
+        if let Some((_, body)) = else_ {
+            self.stml(body)?;
         }
         for jump in end {
             self.settle_jump(jump)?;
@@ -788,8 +1195,8 @@ impl<'a> Compiler<'a> {
 
     fn params(
         &mut self,
-        required: &Vec<Expr>,
-        optional: &Vec<(Expr, Expr)>,
+        required: &[Expr],
+        optional: &[(Expr, Expr)],
         variadic: &Option<(Rc<Token>, Box<Expr>)>,
     ) -> Result<(Arity, Vec<usize>, usize), ()> {
         let mut defaults = vec![];
@@ -801,13 +1208,13 @@ impl<'a> Compiler<'a> {
         if let Some((token, definable)) = variadic {
             self.chunk
                 .write_instr_no_operands(BUILD_VARIADIC, Rc::clone(token));
-            self.definable(definable, false)?
+            self.definable(definable, false, false)?
         }
         for (definable, _) in optional.iter().rev() {
-            self.definable(definable, false)?
+            self.definable(definable, false, false)?
         }
         for definable in required.iter().rev() {
-            self.definable(definable, false)?
+            self.definable(definable, false, false)?
         }
         Ok((
             Arity::new(
@@ -827,15 +1234,24 @@ impl<'a> Compiler<'a> {
     fn function(
         &mut self,
         body: &Stml,
-        required: &Vec<Expr>,
-        optional: &Vec<(Expr, Expr)>,
+        required: &[Expr],
+        optional: &[(Expr, Expr)],
         variadic: &Option<(Rc<Token>, Box<Expr>)>,
         name: Option<Rc<Token>>,
         token: Rc<Token>,
+        method: bool,
     ) -> Result<(), ()> {
-        let mut compiler = Compiler::new_function(Rc::clone(&token), body, Rc::clone(&self.locals));
+        let mut compiler = Compiler::new_function(
+            Rc::clone(&token),
+            body,
+            Rc::clone(&self.locals),
+            Rc::clone(&self.modules),
+        );
         let (arity, defaults, body) = compiler.params(required, optional, variadic)?;
-        if let Some(token) = &name {
+        if method {
+            // Methods are called with their receivers in place of themselves.
+            compiler.define_hidden(Rc::clone(&token), THIS)?
+        } else if let Some(token) = &name {
             compiler.define(Rc::clone(token))?
         } else {
             compiler
@@ -854,13 +1270,14 @@ impl<'a> Compiler<'a> {
         let upvalues = compiler.locals.borrow().upvalues.clone();
         self.write_closure(
             token,
-            value::Function::new(
+            Rc::new(value::Function::new(
                 name.map(|token| token.lexeme().to_owned()),
                 chunk,
                 arity,
                 defaults,
                 body,
-            ),
+                compiler.generator,
+            )),
             upvalues,
         )?;
         Ok(())
@@ -871,10 +1288,10 @@ impl<'a> Compiler<'a> {
         export_token: &Option<Rc<Token>>,
         token: Rc<Token>,
         name: Rc<Token>,
-        required: &Vec<Expr>,
-        optional: &Vec<(Expr, Expr)>,
+        required: &[Expr],
+        optional: &[(Expr, Expr)],
         variadic: &Option<(Rc<Token>, Box<Expr>)>,
-        body: &Box<Stml>,
+        body: &Stml,
     ) -> Result<(), ()> {
         self.function(
             body,
@@ -883,14 +1300,65 @@ impl<'a> Compiler<'a> {
             variadic,
             Some(Rc::clone(&name)),
             token,
+            false,
         )?;
         match export_token {
-            Some(_) => self.export(name)?,
+            Some(_) => self.export(name, false)?,
             None => self.define(name)?,
         }
         Ok(())
     }
 
+    fn class_decl(
+        &mut self,
+        export_token: &Option<Rc<Token>>,
+        token: Rc<Token>,
+        name: Rc<Token>,
+        parent: &Option<(Rc<Token>, Expr)>,
+        methods: &[Stml],
+    ) -> Result<(), ()> {
+        self.write_string_of_ident(Rc::clone(&name))?;
+        self.chunk.write_instr_no_operands(CLASS, Rc::clone(&token));
+        match export_token {
+            Some(_) => self.export(Rc::clone(&name), false)?,
+            None => self.define(Rc::clone(&name))?,
+        }
+        if let Some((from_token, parent)) = parent {
+            // The parent is kept in a hidden local for the methods to capture.
+            self.start_scope();
+            self.expr(parent)?;
+            self.define_hidden(Rc::clone(from_token), SUPER)?;
+            self.get(&Expr::Variable(Rc::clone(&name)))?;
+            self.get_hidden(Rc::clone(from_token), SUPER)?;
+            self.chunk
+                .write_instr_no_operands(INHERIT, Rc::clone(from_token));
+        } else {
+            self.get(&Expr::Variable(Rc::clone(&name)))?;
+        }
+        for method in methods {
+            match method {
+                Stml::FunctionDecl(_, token, name, required, optional, variadic, body) => {
+                    self.function(
+                        body,
+                        required,
+                        optional,
+                        variadic,
+                        Some(Rc::clone(name)),
+                        Rc::clone(token),
+                        true,
+                    )?;
+                    self.chunk.write_instr_no_operands(METHOD, Rc::clone(name));
+                }
+                _ => unreachable!(),
+            }
+        }
+        self.chunk.write_instr_no_operands(POP, Rc::clone(&token));
+        if parent.is_some() {
+            self.end_scope(token);
+        }
+        Ok(())
+    }
+
     fn return_stml(&mut self, token: Rc<Token>, value: &Option<Expr>) -> Result<(), ()> {
         if self.typ != CompilerType::Function {
             self.err(CompileError::ReturnOutsideFunction(Rc::clone(&token)));
@@ -900,10 +1368,25 @@ impl<'a> Compiler<'a> {
             Some(expr) => self.expr(expr)?,
             None => self.nil(Rc::clone(&token)),
         };
+        self.exit_handlers(Rc::clone(&token), 0)?;
         self.chunk.write_instr_no_operands(RET, token);
         Ok(())
     }
 
+    fn yield_stml(&mut self, token: Rc<Token>, value: &Option<Expr>) -> Result<(), ()> {
+        if self.typ != CompilerType::Function {
+            self.err(CompileError::YieldOutsideFunction(Rc::clone(&token)));
+            return Err(());
+        }
+        self.generator = true;
+        match value {
+            Some(expr) => self.expr(expr)?,
+            None => self.nil(Rc::clone(&token)),
+        };
+        self.chunk.write_instr_no_operands(YIELD, token);
+        Ok(())
+    }
+
     fn throw_stml(&mut self, token: Rc<Token>, value: &Option<Expr>) -> Result<(), ()> {
         match value {
             Some(expr) => self.expr(expr)?,
@@ -913,33 +1396,58 @@ impl<'a> Compiler<'a> {
         Ok(())
     }
 
-    fn settle_breaks(&mut self) -> Result<(), ()> {
-        while let Some(ip) = self.breaks.pop() {
+    fn start_loop(&mut self, label: &Option<Rc<Token>>) {
+        self.loops.push(Loop {
+            label: label.as_ref().map(|label| label.lexeme().to_owned()),
+            start: self.ip(),
+            depth: self.locals.borrow().depth,
+            handlers: self.handlers.len(),
+            tmps: self.tmps,
+            breaks: vec![],
+        })
+    }
+
+    /// Pops the innermost loop and settles its breaks.
+    fn end_loop(&mut self) -> Result<(), ()> {
+        for ip in self.loops.pop().unwrap().breaks {
             self.settle_jump(ip)?
         }
         Ok(())
     }
 
-    fn loop_stml(&mut self, token: Rc<Token>, body: &Stml) -> Result<(), ()> {
+    fn loop_stml(
+        &mut self,
+        label: &Option<Rc<Token>>,
+        token: Rc<Token>,
+        body: &Stml,
+    ) -> Result<(), ()> {
+        self.start_loop(label);
         let start = self.ip();
-        self.loops.push(start);
         self.stml(body)?;
         self.write_loop(token, start)?;
-        self.settle_breaks()
+        self.end_loop()
     }
-    fn while_stml(&mut self, token: Rc<Token>, condition: &Expr, body: &Stml) -> Result<(), ()> {
+
+    fn while_stml(
+        &mut self,
+        label: &Option<Rc<Token>>,
+        token: Rc<Token>,
+        condition: &Expr,
+        body: &Stml,
+    ) -> Result<(), ()> {
+        self.start_loop(label);
         let start = self.ip();
-        self.loops.push(start);
         self.expr(condition)?;
         let falsy_condition = self.chunk.write_jump(POP_JUMP_IF_FALSY, Rc::clone(&token));
         self.stml(body)?;
         self.write_loop(token, start)?;
         self.settle_jump(falsy_condition)?;
-        self.settle_breaks()
+        self.end_loop()
     }
 
     fn for_in_stml(
         &mut self,
+        label: &Option<Rc<Token>>,
         token: Rc<Token>,
         definable: &Expr,
         _: Rc<Token>,
@@ -948,59 +1456,381 @@ impl<'a> Compiler<'a> {
     ) -> Result<(), ()> {
         self.expr(iterable)?;
         self.chunk.write_instr_no_operands(ITER, Rc::clone(&token));
+        self.tmps += 1;
+        self.start_loop(label);
         let start = self.ip();
-        self.loops.push(start);
-        let iterator_stopped = self.chunk.write_jump(FOR_ITER, token);
+        let iterator_stopped = self.chunk.write_jump(FOR_ITER, Rc::clone(&token));
         match body {
             Stml::Block(token, stmls) => {
                 self.start_scope();
-                self.definable(definable, false)?;
+                self.definable(definable, false, false)?;
                 self.stmls(stmls);
                 self.end_scope(Rc::clone(token));
                 self.write_loop(Rc::clone(token), start)?
             }
             _ => unreachable!(),
         }
-        self.settle_jump(iterator_stopped)?;
-        self.settle_breaks()
+        self.tmps -= 1;
+        // Breaking leaves the iterator behind unlike stopping.
+        self.end_loop()?;
+        self.chunk.write_instr_no_operands(POP, token);
+        self.settle_jump(iterator_stopped)
     }
 
-    fn break_stml(&mut self, token: Rc<Token>) -> Result<(), ()> {
-        if !self.in_loop() {
-            self.err(CompileError::OutsideLoopBreak(token));
-            return Err(());
+    fn match_stml(
+        &mut self,
+        token: Rc<Token>,
+        subject: &Expr,
+        arms: &[(Expr, Option<Expr>, Stml)],
+        default: &Option<(Rc<Token>, Box<Stml>)>,
+    ) -> Result<(), ()> {
+        self.expr(subject)?;
+        self.tmps += 1;
+        let mut matched = vec![];
+        for (pattern, guard, body) in arms {
+            // 1. Checking (the subject stays as TOT)
+            let mut failed = vec![];
+            self.pattern_checks(pattern, &mut vec![], &mut failed)?;
+            // 2. Binding
+            self.start_scope();
+            self.pattern_bindings(pattern, &mut vec![])?;
+            if let Some(guard) = guard {
+                self.expr(guard)?;
+                let passed = self.chunk.write_jump(POP_JUMP_IF_TRUTHY, guard.token());
+                let depth = self.locals.borrow().depth - 1;
+                self.unwind_scopes(guard.token(), depth);
+                failed.push(self.chunk.write_jump(JUMP, guard.token()));
+                self.settle_jump(passed)?;
+            }
+            match body {
+                Stml::Block(token, stmls) => {
+                    self.stmls(stmls);
+                    self.end_scope(Rc::clone(token));
+                }
+                _ => unreachable!(),
+            }
+            matched.push(self.chunk.write_jump(JUMP, Rc::clone(&token)));
+            for jump in failed {
+                self.settle_jump(jump)?
+            }
         }
-        self.breaks.push(self.ip());
+        if let Some((_, body)) = default {
+            self.stml(body)?
+        }
+        self.tmps -= 1;
+        for jump in matched {
+            self.settle_jump(jump)?
+        }
+        self.chunk.write_instr_no_operands(POP, token);
         Ok(())
     }
 
-    fn continue_stml(&mut self, token: Rc<Token>) -> Result<(), ()> {
-        if !self.in_loop() {
-            self.err(CompileError::OutsideLoopContinue(token));
-            return Err(());
+    /// Pushes the value at `path` inside the subject (TOT) without popping the subject.
+    fn pattern_path(&mut self, token: Rc<Token>, path: &[(Rc<Token>, Value)]) -> Result<(), ()> {
+        self.chunk.write_instr_no_operands(DUP, token);
+        for (token, key) in path {
+            self.write_const(Rc::clone(token), key.clone())?;
+            self.chunk.write_instr_no_operands(GET, Rc::clone(token));
+        }
+        Ok(())
+    }
+
+    /// Writes the checks of `pattern` against the value at `path`, collecting the jumps taken when they fail in `failed`.
+    fn pattern_checks(
+        &mut self,
+        pattern: &Expr,
+        path: &mut Vec<(Rc<Token>, Value)>,
+        failed: &mut Vec<usize>,
+    ) -> Result<(), ()> {
+        match pattern {
+            Expr::Variable(..) => {}
+            Expr::Literal(
+                Literal::Number(token)
+                | Literal::String(token)
+                | Literal::Bool(token)
+                | Literal::Nil(token),
+            ) => {
+                self.pattern_path(Rc::clone(token), path)?;
+                self.expr(pattern)?;
+                self.chunk.write_instr_no_operands(EQ, Rc::clone(token));
+                failed.push(self.chunk.write_jump(POP_JUMP_IF_FALSY, Rc::clone(token)));
+            }
+            Expr::Unary(op, expr)
+                if op.typ() == TokenType::Minus
+                    && matches!(**expr, Expr::Literal(Literal::Number(..))) =>
+            {
+                self.pattern_path(Rc::clone(op), path)?;
+                self.expr(pattern)?;
+                self.chunk.write_instr_no_operands(EQ, Rc::clone(op));
+                failed.push(self.chunk.write_jump(POP_JUMP_IF_FALSY, Rc::clone(op)));
+            }
+            Expr::Literal(Literal::List(token, exprs)) => {
+                let (exprs, rest) = match exprs.split_last() {
+                    Some((Expr::Spread(_, rest), exprs)) => {
+                        if !matches!(**rest, Expr::Variable(..)) {
+                            self.err(CompileError::InvalidPattern(rest.token()));
+                            return Err(());
+                        }
+                        (exprs, true)
+                    }
+                    _ => (exprs.as_slice(), false),
+                };
+                self.pattern_path(Rc::clone(token), path)?;
+                self.write_list_pattern(
+                    if rest { MATCH_LIST_REST } else { MATCH_LIST },
+                    Rc::clone(token),
+                    exprs.len(),
+                )?;
+                failed.push(self.chunk.write_jump(POP_JUMP_IF_FALSY, Rc::clone(token)));
+                for (idx, expr) in exprs.iter().enumerate() {
+                    path.push((expr.token(), Value::Number(idx as f64)));
+                    self.pattern_checks(expr, path, failed)?;
+                    path.pop();
+                }
+            }
+            Expr::Literal(Literal::Object(token, props)) => {
+                self.pattern_path(Rc::clone(token), path)?;
+                self.chunk
+                    .write_instr_no_operands(MATCH_HASH_MAP, Rc::clone(token));
+                failed.push(self.chunk.write_jump(POP_JUMP_IF_FALSY, Rc::clone(token)));
+                for (key, value, default) in props {
+                    if let Some((op, _)) = default {
+                        self.err(CompileError::InvalidPattern(Rc::clone(op)));
+                        return Err(());
+                    }
+                    if let Some(Expr::Spread(op, _)) = value {
+                        self.err(CompileError::InvalidPattern(Rc::clone(op)));
+                        return Err(());
+                    }
+                    self.pattern_path(Rc::clone(key), path)?;
+                    self.write_const(Rc::clone(key), Value::from(key.lexeme()))?;
+                    self.chunk
+                        .write_instr_no_operands(MATCH_KEY, Rc::clone(key));
+                    failed.push(self.chunk.write_jump(POP_JUMP_IF_FALSY, Rc::clone(key)));
+                    if let Some(value) = value {
+                        path.push((Rc::clone(key), Value::from(key.lexeme())));
+                        self.pattern_checks(value, path, failed)?;
+                        path.pop();
+                    }
+                }
+            }
+            expr => {
+                self.err(CompileError::InvalidPattern(expr.token()));
+                return Err(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Defines the variables of `pattern` which must have been checked by `pattern_checks`.
+    fn pattern_bindings(
+        &mut self,
+        pattern: &Expr,
+        path: &mut Vec<(Rc<Token>, Value)>,
+    ) -> Result<(), ()> {
+        match pattern {
+            Expr::Variable(token) => {
+                self.pattern_path(Rc::clone(token), path)?;
+                self.define(Rc::clone(token))?
+            }
+            Expr::Literal(Literal::List(_, exprs)) => {
+                for (idx, expr) in exprs.iter().enumerate() {
+                    match expr {
+                        Expr::Spread(op, rest) => {
+                            self.pattern_path(Rc::clone(op), path)?;
+                            self.write_list_pattern(LIST_REST, Rc::clone(op), idx)?;
+                            self.define(rest.token())?
+                        }
+                        _ => {
+                            path.push((expr.token(), Value::Number(idx as f64)));
+                            self.pattern_bindings(expr, path)?;
+                            path.pop();
+                        }
+                    }
+                }
+            }
+            Expr::Literal(Literal::Object(_, props)) => {
+                for (key, value, _) in props {
+                    path.push((Rc::clone(key), Value::from(key.lexeme())));
+                    match value {
+                        Some(value) => self.pattern_bindings(value, path)?,
+                        None => self.pattern_bindings(&Expr::Variable(Rc::clone(key)), path)?,
+                    }
+                    path.pop();
+                }
+            }
+            _ => {}
         }
-        self.write_loop(token, *self.loops.last().unwrap())
+        Ok(())
+    }
+
+    /// Finds the loop that `label` names or the innermost one if it's `None`.
+    fn resolve_loop(&mut self, token: Rc<Token>, label: &Option<Rc<Token>>) -> Result<usize, ()> {
+        let idx = match label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|loop_| loop_.label.as_deref() == Some(label.lexeme())),
+            None => self.loops.len().checked_sub(1),
+        };
+        match (idx, label) {
+            (Some(idx), _) => Ok(idx),
+            (None, Some(label)) => {
+                self.err(CompileError::UnknownLabel(Rc::clone(label)));
+                Err(())
+            }
+            (None, None) if token.typ() == TokenType::Break => {
+                self.err(CompileError::OutsideLoopBreak(token));
+                Err(())
+            }
+            (None, None) => {
+                self.err(CompileError::OutsideLoopContinue(token));
+                Err(())
+            }
+        }
+    }
+
+    /// Unwinds what's between the jump and the loop at `idx`, which are the handlers, the locals, and the tmps.
+    fn exit_to_loop(&mut self, token: Rc<Token>, idx: usize) -> Result<(), ()> {
+        let Loop {
+            depth,
+            handlers,
+            tmps,
+            ..
+        } = self.loops[idx];
+        self.exit_handlers(Rc::clone(&token), handlers)?;
+        self.unwind_scopes(Rc::clone(&token), depth);
+        for _ in tmps..self.tmps {
+            self.chunk.write_instr_no_operands(POP, Rc::clone(&token))
+        }
+        Ok(())
+    }
+
+    fn break_stml(&mut self, token: Rc<Token>, label: &Option<Rc<Token>>) -> Result<(), ()> {
+        let idx = self.resolve_loop(Rc::clone(&token), label)?;
+        self.exit_to_loop(Rc::clone(&token), idx)?;
+        let jump = self.chunk.write_jump(JUMP, token);
+        self.loops[idx].breaks.push(jump);
+        Ok(())
+    }
+
+    fn continue_stml(&mut self, token: Rc<Token>, label: &Option<Rc<Token>>) -> Result<(), ()> {
+        let idx = self.resolve_loop(Rc::clone(&token), label)?;
+        self.exit_to_loop(Rc::clone(&token), idx)?;
+        self.write_loop(token, self.loops[idx].start)
+    }
+
+    /// Pops the handlers after the first `depth` ones, running the bodies of the `أخيراً` blocks among them.
+    fn exit_handlers(&mut self, token: Rc<Token>, depth: usize) -> Result<(), ()> {
+        for idx in (depth..self.handlers.len()).rev() {
+            self.chunk
+                .write_instr_no_operands(POP_HANDLER, Rc::clone(&token));
+            if let Some(finally_body) = self.handlers[idx].clone() {
+                // Exiting from inside the body itself only runs the handlers outside it.
+                let inner = self.handlers.split_off(idx);
+                let res = self.stml(&finally_body);
+                self.handlers.extend(inner);
+                res?
+            }
+        }
+        Ok(())
     }
 
     fn try_catch_stml(
         &mut self,
         token: Rc<Token>,
         body: &Stml,
-        _: Rc<Token>,
-        err: Rc<Token>,
-        catch_body: &Stml,
+        catches: &[(Rc<Token>, Rc<Token>, Vec<Rc<Token>>, Stml)],
+        finally: &Option<(Rc<Token>, Box<Stml>)>,
     ) -> Result<(), ()> {
-        let caught = self.chunk.write_jump(APPEND_HANDLER, token);
-        self.stml(body)?;
-        self.settle_jump(caught)?;
-        match catch_body {
-            Stml::Block(token, stmls) => {
-                self.start_scope();
-                self.define(err)?;
-                self.stmls(stmls);
-                self.end_scope(Rc::clone(token))
+        // The `أخيراً` handler is outside the `أمسك` one so that it handles the errors thrown by the catch block as well.
+        let finally_caught = match finally {
+            Some((_, finally_body)) => {
+                self.handlers.push(Some((**finally_body).clone()));
+                Some(self.chunk.write_jump(APPEND_HANDLER, Rc::clone(&token)))
+            }
+            None => None,
+        };
+        if catches.is_empty() {
+            self.stml(body)?;
+        } else {
+            self.handlers.push(None);
+            let caught = self.chunk.write_jump(APPEND_HANDLER, Rc::clone(&token));
+            self.stml(body)?;
+            self.handlers.pop();
+            self.chunk
+                .write_instr_no_operands(POP_HANDLER, Rc::clone(&token));
+            let mut handled = vec![self.chunk.write_jump(JUMP, Rc::clone(&token))];
+            self.settle_jump(caught)?;
+            // The error stays as TOT until a catch block that handles its kind is found.
+            let mut caught_all = false;
+            for (catch_token, err, kinds, catch_body) in catches {
+                let unhandled = if kinds.is_empty() {
+                    caught_all = true;
+                    None
+                } else {
+                    let mut matched = vec![];
+                    for kind in kinds {
+                        if !ERROR_KINDS.contains(&kind.lexeme()) {
+                            self.err(CompileError::UnknownErrorKind(Rc::clone(kind)));
+                            return Err(());
+                        }
+                        self.chunk.write_instr_no_operands(DUP, Rc::clone(kind));
+                        self.write_const(Rc::clone(kind), Value::from(KIND))?;
+                        self.chunk.write_instr_no_operands(GET, Rc::clone(kind));
+                        self.write_string_of_ident(Rc::clone(kind))?;
+                        self.chunk.write_instr_no_operands(EQ, Rc::clone(kind));
+                        matched.push(self.chunk.write_jump(POP_JUMP_IF_TRUTHY, Rc::clone(kind)));
+                    }
+                    let unhandled = self.chunk.write_jump(JUMP, Rc::clone(catch_token));
+                    for jump in matched {
+                        self.settle_jump(jump)?
+                    }
+                    Some(unhandled)
+                };
+                self.chunk
+                    .write_instr_no_operands(CATCH, Rc::clone(catch_token));
+                match catch_body {
+                    Stml::Block(token, stmls) => {
+                        self.start_scope();
+                        self.define(Rc::clone(err))?;
+                        self.stmls(stmls);
+                        self.end_scope(Rc::clone(token))
+                    }
+                    _ => unreachable!(),
+                }
+                handled.push(self.chunk.write_jump(JUMP, Rc::clone(catch_token)));
+                if let Some(unhandled) = unhandled {
+                    self.settle_jump(unhandled)?
+                }
+                if caught_all {
+                    break;
+                }
+            }
+            if !caught_all {
+                self.chunk
+                    .write_instr_no_operands(RETHROW, Rc::clone(&token));
+            }
+            for jump in handled {
+                self.settle_jump(jump)?
             }
-            _ => unreachable!(),
+        }
+        if let (Some((finally_token, finally_body)), Some(finally_caught)) =
+            (finally, finally_caught)
+        {
+            self.handlers.pop();
+            self.chunk
+                .write_instr_no_operands(POP_HANDLER, Rc::clone(finally_token));
+            self.stml(finally_body)?;
+            let skip_rethrow = self.chunk.write_jump(JUMP, Rc::clone(finally_token));
+            // The error stays as TOT while the body runs.
+            self.settle_jump(finally_caught)?;
+            self.tmps += 1;
+            self.stml(finally_body)?;
+            self.tmps -= 1;
+            self.chunk
+                .write_instr_no_operands(RETHROW, Rc::clone(finally_token));
+            self.settle_jump(skip_rethrow)?;
         }
         Ok(())
     }
@@ -1017,12 +1847,85 @@ impl<'a> Compiler<'a> {
             return Err(());
         }
         let path = {
-            let tmp = self.quoted_string(path)?;
+            let tmp = self.string(path)?;
+            if let Some(name) = tmp.strip_prefix('@') {
+                if !NATIVE_MODULES.contains(&name) {
+                    self.err(CompileError::UnknownNativeModule(token, name.to_owned()));
+                    return Err(());
+                }
+                self.write_instr_const(
+                    (NATIVE_MODULE8, NATIVE_MODULE16),
+                    Rc::clone(&token),
+                    Value::from(name),
+                )?;
+                return self.definable(definable, false, false);
+            }
             match token.path() {
-                Some(path) => path.parent().unwrap_or(&Path::new("")).join(tmp),
+                Some(path) => path.parent().unwrap_or(Path::new("")).join(tmp),
                 None => PathBuf::from(tmp),
             }
         };
+        let canonical_path = fs::canonicalize(&path)
+            .map_err(|err| self.err(CompileError::Io(Rc::clone(&token), Rc::new(err))))?;
+        let cycle = {
+            let modules = self.modules.borrow();
+            modules
+                .importing
+                .iter()
+                .position(|(importing, _)| *importing == canonical_path)
+                .map(|idx| {
+                    modules.importing[idx..]
+                        .iter()
+                        .map(|(_, path)| normalize(path))
+                        .collect::<Vec<_>>()
+                })
+        };
+        if let Some(mut paths) = cycle {
+            paths.push(normalize(&path));
+            self.err(CompileError::ImportCycle(token, paths));
+            return Err(());
+        }
+        let compiled = self.modules.borrow().compiled.get(&canonical_path).cloned();
+        let (function, constants) = match compiled {
+            Some(compiled) => compiled,
+            None => {
+                let compiled = self.compile_module(Rc::clone(&token), path)?;
+                self.modules
+                    .borrow_mut()
+                    .compiled
+                    .insert(canonical_path.clone(), compiled.clone());
+                compiled
+            }
+        };
+        self.write_closure(Rc::clone(&token), function, vec![])?;
+        self.write_instr_const(
+            (IMPORT8, IMPORT16),
+            Rc::clone(&token),
+            Value::from(canonical_path.to_string_lossy().as_ref()),
+        )?;
+        match definable {
+            // The names destructured out of constant exports stay constant.
+            Expr::Literal(Literal::Object(token, props)) => {
+                self.unpack_hash_map(Rc::clone(token), props)?;
+                for (key, value, _) in props {
+                    let constant = constants.iter().any(|name| name == key.lexeme());
+                    match value {
+                        Some(expr) => self.definable(expr, false, constant)?,
+                        None => self.declare(Rc::clone(key), constant)?,
+                    }
+                }
+            }
+            _ => self.definable(definable, false, false)?,
+        }
+        Ok(())
+    }
+
+    /// Returns the module's function along with the names of its constant exports.
+    fn compile_module(
+        &mut self,
+        token: Rc<Token>,
+        path: PathBuf,
+    ) -> Result<(Rc<value::Function>, Vec<String>), ()> {
         let source = fs::read_to_string(&path)
             .map_err(|err| self.err(CompileError::Io(Rc::clone(&token), Rc::new(err))))?;
         let tokens = Lexer::new(source, Some(&path)).lex();
@@ -1030,21 +1933,23 @@ impl<'a> Compiler<'a> {
         let ast = Parser::new(tokens)
             .parse()
             .map_err(|errors| self.err(CompileError::ModuleParser(Rc::clone(&token), errors)))?;
-        let chunk = Compiler::new(CompilerType::Module, &ast, Rc::clone(&token))
-            .compile()
-            .map_err(|errors| {
-                for err in errors {
-                    self.err(err)
-                }
-            })?;
-        self.write_closure(
-            Rc::clone(&token),
-            value::Function::new(None, chunk, Arity::default(), vec![], 0),
-            vec![],
-        )?;
-        self.write_call_unchecked(token, 0);
-        self.definable(definable, false)?;
-        Ok(())
+        let mut compiler = Compiler::new_module(&ast, token, Rc::clone(&self.modules));
+        let chunk = compiler.compile().map_err(|errors| {
+            for err in errors {
+                self.err(err)
+            }
+        })?;
+        Ok((
+            Rc::new(value::Function::new(
+                None,
+                chunk,
+                Arity::default(),
+                vec![],
+                0,
+                false,
+            )),
+            compiler.constant_exports(),
+        ))
     }
 
     fn stml(&mut self, stml: &Stml) -> Result<(), ()> {
@@ -1077,26 +1982,34 @@ impl<'a> Compiler<'a> {
             }
             Stml::Return(token, value) => self.return_stml(Rc::clone(token), value)?,
             Stml::Throw(token, value) => self.throw_stml(Rc::clone(token), value)?,
-            Stml::While(token, condition, body) => {
-                self.while_stml(Rc::clone(token), condition, body)?
+            Stml::Yield(token, value) => self.yield_stml(Rc::clone(token), value)?,
+            Stml::While(label, token, condition, body) => {
+                self.while_stml(label, Rc::clone(token), condition, body)?
             }
-            Stml::Loop(token, body) => self.loop_stml(Rc::clone(token), body)?,
-            Stml::ForIn(token, definable, in_token, iterable, body) => self.for_in_stml(
+            Stml::Loop(label, token, body) => self.loop_stml(label, Rc::clone(token), body)?,
+            Stml::ForIn(label, token, definable, in_token, iterable, body) => self.for_in_stml(
+                label,
                 Rc::clone(token),
                 definable,
                 Rc::clone(in_token),
                 iterable,
                 body,
             )?,
-            Stml::Break(token) => self.break_stml(Rc::clone(token))?,
-            Stml::Continue(token) => self.continue_stml(Rc::clone(token))?,
-            Stml::TryCatch(token, body, catch_token, err, catch_body) => self.try_catch_stml(
+            Stml::ClassDecl(export_token, token, name, parent, methods) => self.class_decl(
+                export_token,
                 Rc::clone(token),
-                body,
-                Rc::clone(catch_token),
-                Rc::clone(err),
-                catch_body,
+                Rc::clone(name),
+                parent,
+                methods,
             )?,
+            Stml::Match(token, subject, arms, default) => {
+                self.match_stml(Rc::clone(token), subject, arms, default)?
+            }
+            Stml::Break(token, label) => self.break_stml(Rc::clone(token), label)?,
+            Stml::Continue(token, label) => self.continue_stml(Rc::clone(token), label)?,
+            Stml::TryCatch(token, body, catches, finally) => {
+                self.try_catch_stml(Rc::clone(token), body, catches, finally)?
+            }
             Stml::Import(token, definable, from_token, path) => self.import_stml(
                 Rc::clone(token),
                 definable,
@@ -1114,12 +2027,35 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    /// The names of the exported locals that were declared with `ثابت`.
+    fn constant_exports(&self) -> Vec<String> {
+        let locals = self.locals.borrow();
+        (0..locals.len())
+            .map(|idx| locals.get(idx))
+            .filter(|local| local.exported && local.constant)
+            .map(|local| local.name.clone())
+            .collect()
+    }
+
     #[allow(unused_must_use)]
     pub fn compile(&mut self) -> Result<Chunk, Vec<CompileError>> {
         if cfg!(feature = "verbose") && self.typ == CompilerType::Script {
             println!("[COMPILER] started")
         }
+        // Marks the file as being compiled, so that importing it from one of its imports fails.
+        let importing = match (self.typ, self.token.path()) {
+            (CompilerType::Function, _) | (_, None) => None,
+            (_, Some(path)) => fs::canonicalize(path)
+                .ok()
+                .map(|canonical_path| (canonical_path, path.clone())),
+        };
+        if let Some(importing) = &importing {
+            self.modules.borrow_mut().importing.push(importing.clone());
+        }
         self.stmls(self.ast);
+        if importing.is_some() {
+            self.modules.borrow_mut().importing.pop();
+        }
         match self.typ {
             CompilerType::Script => {}
             CompilerType::Function => {
@@ -1135,7 +2071,7 @@ impl<'a> Compiler<'a> {
                     if local.exported {
                         self.write_const(
                             Rc::clone(&local.token),
-                            Value::from(local.token.lexeme().clone()),
+                            Value::from(local.token.lexeme()),
                         );
                         self.write_instr_idx(GET_LOCAL, Rc::clone(&local.token), idx);
                         size += 1;
@@ -1144,11 +2080,17 @@ impl<'a> Compiler<'a> {
                 self.chunk
                     .write_build(BUILD_HASH_MAP, Rc::clone(&self.token), size)
                     .map_err(|_| CompileError::TooManyExports(Rc::clone(&self.token))); // ?
+                let constants = self.constant_exports();
+                for name in &constants {
+                    self.write_const(Rc::clone(&self.token), Value::from(name.as_str()));
+                }
+                self.chunk
+                    .write_build(BUILD_MODULE, Rc::clone(&self.token), constants.len());
                 self.chunk
                     .write_instr_no_operands(RET, Rc::clone(&self.token))
             }
         }
-        if self.errors.len() > 0 {
+        if !self.errors.is_empty() {
             if cfg!(feature = "verbose") && self.typ == CompilerType::Script {
                 println!("[COMPILER] failed")
             }
@@ -14,6 +14,100 @@ fn is_newline(c: char) -> bool {
     ['\u{000a}', '\u{000d}', '\u{2028}', '\u{2029}'].contains(&c)
 }
 
+/// Matches both ASCII digits and Arabic-Indic ones (i.e., `٠` to `٩`).
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit() || ('\u{0660}'..='\u{0669}').contains(&c)
+}
+
+/// Matches `.` and the Arabic decimal separator `٫`.
+fn is_decimal_separator(c: char) -> bool {
+    c == '.' || c == '\u{066b}'
+}
+
+/// Returns the value of `c` as a digit in `radix`, treating Arabic-Indic digits like their ASCII counterparts.
+pub fn to_digit(c: char, radix: u32) -> Option<u32> {
+    match c {
+        '\u{0660}'..='\u{0669}' => Some(c as u32 - '\u{0660}' as u32).filter(|d| *d < radix),
+        c => c.to_digit(radix),
+    }
+}
+
+/// Returns the radix a number prefix (i.e., the `x` in `0x`) stands for.
+pub fn radix(prefix: char) -> Option<u32> {
+    match prefix {
+        'x' => Some(16),
+        'o' => Some(8),
+        'b' => Some(2),
+        _ => None,
+    }
+}
+
+/// Checks that `digits` is a non-empty run of `radix` digits where every `_` sits between two digits.
+fn is_valid_digits(digits: &str, radix: u32) -> bool {
+    !digits.is_empty()
+        && !digits.starts_with('_')
+        && !digits.ends_with('_')
+        && !digits.contains("__")
+        && digits
+            .chars()
+            .all(|c| c == '_' || to_digit(c, radix).is_some())
+}
+
+fn is_valid_number(lexeme: &str) -> bool {
+    let mut chars = lexeme.chars();
+    chars.next();
+    if let Some(radix) = chars.next().and_then(radix) {
+        return is_valid_digits(chars.as_str(), radix);
+    }
+    match lexeme.split_once(is_decimal_separator) {
+        Some((integer, fraction)) => is_valid_digits(integer, 10) && is_valid_digits(fraction, 10),
+        None => is_valid_digits(lexeme, 10),
+    }
+}
+
+/// Returns the offset of the last character in `source`.
+fn last_offset(source: &str) -> usize {
+    source.char_indices().last().map_or(0, |(offset, _)| offset)
+}
+
+fn keyword(lexeme: &str) -> Option<TokenType> {
+    use TokenType::*;
+
+    Some(match lexeme {
+        "إن" => If,
+        "وإن" => ElseIf,
+        "إلا" => Else,
+        "دالة" => Function,
+        "متغير" => Var,
+        "ثابت" => Const,
+        "كرر" => Loop,
+        "طالما" => While,
+        "إكسر" => Break,
+        "واصل" => Continue,
+        "أرجع" => Return,
+        "ألقي" => Throw,
+        "حاول" => Try,
+        "أمسك" => Catch,
+        "أخيراً" => Finally,
+        "عدم" => Nil,
+        "صحيح" => True,
+        "خطأ" => False,
+        "استورد" => Import,
+        "من" => From,
+        "صدّر" => Export,
+        "لكل" => For,
+        "في" => In,
+        "طابق" => Match,
+        "صنف" => Class,
+        "هذا" => This,
+        "أصل" => Super,
+        "أنتج" => Yield,
+        "و" => And,
+        "أو" => Or,
+        _ => return None,
+    })
+}
+
 pub struct Lexer {
     source: Rc<String>,
     path: Option<PathBuf>,
@@ -30,7 +124,8 @@ impl Lexer {
     /// Creates a new token returning it.
     ///
     /// `first` represents the offset of the first character, while `last` represents the offset of the last.
-    fn pop_token(&mut self, typ: TokenType, first: usize, length: usize) -> Rc<Token> {
+    fn pop_token(&mut self, typ: TokenType, first: usize, last: usize) -> Rc<Token> {
+        let length = last - first + self.source[last..].chars().next().map_or(0, char::len_utf8);
         Rc::new(Token::new(
             typ,
             Rc::clone(&self.source),
@@ -60,7 +155,7 @@ impl Lexer {
     }
 
     fn at_end(char_indices: &mut Peekable<CharIndices>) -> bool {
-        Self::next(char_indices).is_none()
+        Self::peek(char_indices).is_none()
     }
 
     /// If the next character matches `pred`, Advances the iterator returning the next element.
@@ -89,24 +184,90 @@ impl Lexer {
         }
     }
 
+    /// Checks whether the next three characters are quotes without advancing the iterator.
+    fn check_triple_quote(char_indices: &mut Peekable<CharIndices>) -> bool {
+        let mut lookahead = char_indices.clone();
+        (0..3).all(|_| matches!(Self::next(&mut lookahead), Some((_, '"'))))
+    }
+
+    /// Lexes a string starting from its opening quote(s) or from the `}` that ends an interpolated expression.
+    ///
+    /// Returns an `Interpolation` token when the string gets interrupted by an expression (i.e., when it reaches an unescaped `{`).
+    ///
+    /// Triple quoted strings can span multiple lines, and when they aren't terminated only the character at `first` is reported.
+    fn string(
+        &mut self,
+        char_indices: &mut Peekable<CharIndices>,
+        first: usize,
+        triple: bool,
+    ) -> Rc<Token> {
+        use TokenType::*;
+
+        let mut last = first;
+        loop {
+            if triple && Self::check_triple_quote(char_indices) {
+                Self::next(char_indices);
+                Self::next(char_indices);
+                let (last, _) = Self::next(char_indices).unwrap();
+                return self.pop_token(String, first, last);
+            } else if let Some((last, _)) =
+                Self::check_next(char_indices, Box::new(move |c| !triple && c == '"'))
+            {
+                return self.pop_token(String, first, last);
+            } else if let Some((last, _)) = Self::check_next(char_indices, Self::is('{')) {
+                return self.pop_token(Interpolation, first, last);
+            } else if Self::at_end(char_indices) {
+                return self.pop_token(
+                    UnterminatedString,
+                    first,
+                    if triple { first } else { last },
+                );
+            } else if !triple && Self::check(char_indices, Box::new(is_newline)) {
+                return self.pop_token(UnterminatedString, first, last);
+            } else if let Some((offset, _)) = Self::check_next(char_indices, Self::is('\\')) {
+                last = offset;
+                if let Some((offset, c)) =
+                    Self::check_next(char_indices, Box::new(move |c| triple || !is_newline(c)))
+                {
+                    last = offset;
+                    // The braces of `\u{...}` don't start an interpolation.
+                    if c == 'u' && Self::check_next(char_indices, Self::is('{')).is_some() {
+                        while let Some((offset, c)) =
+                            Self::check_next(char_indices, Box::new(|c| c != '"' && !is_newline(c)))
+                        {
+                            last = offset;
+                            if c == '}' {
+                                break;
+                            }
+                        }
+                    }
+                }
+            } else {
+                last = Self::next(char_indices).unwrap().0;
+            }
+        }
+    }
+
     pub fn lex(mut self) -> Vec<Rc<Token>> {
         use TokenType::*;
 
         let source = Rc::clone(&self.source);
         let mut char_indices = source.char_indices().peekable();
         let mut tokens = vec![];
+        // The number of unclosed braces inside each interpolated expression we're currently in, along with whether its string is triple quoted.
+        let mut interpolations: Vec<(usize, bool)> = vec![];
         while let Some((first, c)) = Self::next_no_whitespace(&mut char_indices) {
             macro_rules! single {
                 ($typ:ident) => {
-                    tokens.push(self.pop_token($typ, first, 1))
+                    tokens.push(self.pop_token($typ, first, first))
                 };
             }
             macro_rules! optional_equal {
                 ($without:ident, $with:ident) => {
-                    if Self::check_next(&mut char_indices, Self::is('=')).is_some() {
-                        tokens.push(self.pop_token($with, first, 2))
+                    if let Some((last, _)) = Self::check_next(&mut char_indices, Self::is('=')) {
+                        tokens.push(self.pop_token($with, first, last))
                     } else {
-                        tokens.push(self.pop_token($without, first, 1))
+                        tokens.push(self.pop_token($without, first, first))
                     }
                 };
             }
@@ -115,58 +276,83 @@ impl Lexer {
                 x if is_newline(x) => single!(NewLine),
                 '(' => single!(OParen),
                 ')' => single!(CParen),
-                '{' => single!(OBrace),
-                '}' => single!(CBrace),
+                '{' => {
+                    if let Some((depth, _)) = interpolations.last_mut() {
+                        *depth += 1;
+                    }
+                    single!(OBrace)
+                }
+                '}' => match interpolations.last_mut() {
+                    Some((0, triple)) => {
+                        let triple = *triple;
+                        interpolations.pop();
+                        let token = self.string(&mut char_indices, first, triple);
+                        if token.typ() == Interpolation {
+                            interpolations.push((0, triple));
+                        }
+                        tokens.push(token);
+                    }
+                    Some((depth, _)) => {
+                        *depth -= 1;
+                        single!(CBrace)
+                    }
+                    None => single!(CBrace),
+                },
                 '[' => single!(OBracket),
                 ']' => single!(CBracket),
                 '،' => single!(Comma),
-                '؟' => single!(QuestionMark),
+                '؟' => {
+                    if let Some((last, _)) = Self::check_next(&mut char_indices, Self::is('.')) {
+                        tokens.push(self.pop_token(QuestionPeriod, first, last))
+                    } else if let Some((last, _)) =
+                        Self::check_next(&mut char_indices, Self::is('؟'))
+                    {
+                        tokens.push(self.pop_token(DQuestionMark, first, last))
+                    } else {
+                        single!(QuestionMark)
+                    }
+                }
                 ':' => single!(Colon),
                 '|' => single!(Pipe),
                 '+' => optional_equal!(Plus, PlusEqual),
                 '-' => optional_equal!(Minus, MinusEqual),
-                '*' => optional_equal!(Star, StarEqual),
+                '*' => {
+                    if let Some((last, _)) = Self::check_next(&mut char_indices, Self::is('*')) {
+                        tokens.push(self.pop_token(DStar, first, last))
+                    } else {
+                        optional_equal!(Star, StarEqual)
+                    }
+                }
+                '\\' => single!(BackSlash),
+                '&' => single!(Ampersand),
+                '^' => single!(Caret),
+                '~' => single!(Tilde),
                 '/' => {
-                    if Self::check_next(&mut char_indices, Self::is('=')).is_some() {
-                        tokens.push(self.pop_token(SlashEqual, first, 2))
-                    } else if Self::check_next(&mut char_indices, Self::is('/')).is_some() {
-                        loop {
-                            if let Some((last, _)) =
-                                Self::check_next(&mut char_indices, Box::new(is_newline))
-                            {
-                                tokens.push(self.pop_token(InlineComment, first, first - last + 1));
-                                break;
-                            } else if Self::at_end(&mut char_indices) {
-                                // TODO test
-                                tokens.push(self.pop_token(
-                                    InlineComment,
-                                    first,
-                                    self.source.len() - first,
-                                ));
-                                break;
-                            } else {
-                                Self::next(&mut char_indices);
-                            }
-                        }
-                    } else if let Some((_, _)) = Self::check_next(&mut char_indices, Self::is('*'))
+                    if let Some((last, _)) = Self::check_next(&mut char_indices, Self::is('=')) {
+                        tokens.push(self.pop_token(SlashEqual, first, last))
+                    } else if let Some((mut last, _)) =
+                        Self::check_next(&mut char_indices, Self::is('/'))
                     {
+                        while let Some((offset, _)) =
+                            Self::check_next(&mut char_indices, Box::new(|c| !is_newline(c)))
+                        {
+                            last = offset;
+                        }
+                        tokens.push(self.pop_token(InlineComment, first, last));
+                    } else if Self::check_next(&mut char_indices, Self::is('*')).is_some() {
                         loop {
                             if Self::check_next(&mut char_indices, Self::is('*')).is_some() {
                                 if let Some((last, _)) =
                                     Self::check_next(&mut char_indices, Self::is('/'))
                                 {
-                                    tokens.push(self.pop_token(
-                                        BlockComment,
-                                        first,
-                                        first - last + 1,
-                                    ));
+                                    tokens.push(self.pop_token(BlockComment, first, last));
                                     break;
                                 }
                             } else if Self::at_end(&mut char_indices) {
                                 tokens.push(self.pop_token(
                                     UnterminatedBlockComment,
                                     first,
-                                    source.len() - first,
+                                    last_offset(&source),
                                 ));
                                 break;
                             } else {
@@ -174,48 +360,63 @@ impl Lexer {
                             }
                         }
                     } else {
-                        tokens.push(self.pop_token(Slash, first, 1))
+                        single!(Slash)
                     }
                 }
                 '%' => optional_equal!(Percent, PercentEqual),
                 '!' => optional_equal!(Bang, BangEqual),
                 '=' => optional_equal!(Equal, DEqual),
-                '>' => optional_equal!(Greater, GreaterEqual),
-                '<' => optional_equal!(Less, LessEqual),
+                '>' => {
+                    if let Some((last, _)) = Self::check_next(&mut char_indices, Self::is('>')) {
+                        tokens.push(self.pop_token(DGreater, first, last))
+                    } else {
+                        optional_equal!(Greater, GreaterEqual)
+                    }
+                }
+                '<' => {
+                    if let Some((last, _)) = Self::check_next(&mut char_indices, Self::is('<')) {
+                        tokens.push(self.pop_token(DLess, first, last))
+                    } else {
+                        optional_equal!(Less, LessEqual)
+                    }
+                }
                 '.' => {
                     if let Some((second_first, _)) =
                         Self::check_next(&mut char_indices, Self::is('.'))
                     {
-                        if Self::check_next(&mut char_indices, Self::is('.')).is_some() {
-                            tokens.push(self.pop_token(TPeriod, first, 3))
+                        if let Some((last, _)) = Self::check_next(&mut char_indices, Self::is('.'))
+                        {
+                            tokens.push(self.pop_token(TPeriod, first, last))
                         } else {
-                            tokens.push(self.pop_token(Period, first, 1));
-                            tokens.push(self.pop_token(Period, second_first, 1))
+                            tokens.push(self.pop_token(DPeriod, first, second_first))
                         }
                     } else {
-                        tokens.push(self.pop_token(Period, first, 1))
+                        single!(Period)
                     }
                 }
-                '"' => loop {
-                    if let Some((last, _)) = Self::check_next(&mut char_indices, Self::is('"')) {
-                        tokens.push(self.pop_token(String, first, first - last + 1));
-                        break;
-                    } else if Self::check_next(&mut char_indices, Self::is('\\')).is_some() {
-                        Self::check_next(&mut char_indices, Box::new(|c| c == '"'));
-                    } else if let Some((last, _)) =
-                        Self::check_next(&mut char_indices, Box::new(is_newline))
-                    {
-                        tokens.push(self.pop_token(UnterminatedString, first, first - last + 1));
+                '"' => {
+                    // The opening quote is already consumed, so two more make a triple quote.
+                    let mut lookahead = char_indices.clone();
+                    let triple =
+                        (0..2).all(|_| matches!(Self::next(&mut lookahead), Some((_, '"'))));
+                    if triple {
+                        Self::next(&mut char_indices);
+                        Self::next(&mut char_indices);
+                    }
+                    let token = self.string(&mut char_indices, first, triple);
+                    if token.typ() == Interpolation {
+                        interpolations.push((0, triple));
+                    }
+                    tokens.push(token);
+                }
+                // Raw strings can span multiple lines and don't have escape sequences nor interpolation.
+                '`' => loop {
+                    if let Some((last, _)) = Self::check_next(&mut char_indices, Self::is('`')) {
+                        tokens.push(self.pop_token(String, first, last));
                         break;
-                    } else if Self::at_end(&mut char_indices) {
-                        tokens.push(self.pop_token(
-                            UnterminatedString,
-                            first,
-                            source.len() - first,
-                        ));
+                    } else if Self::next(&mut char_indices).is_none() {
+                        tokens.push(self.pop_token(UnterminatedString, first, first));
                         break;
-                    } else {
-                        Self::next(&mut char_indices);
                     }
                 },
                 x if x.is_alphabetic() || x == '_' => {
@@ -226,26 +427,52 @@ impl Lexer {
                     ) {
                         last = offset;
                     }
-                    tokens.push(self.pop_token(Identifier, first, last))
-                }
-                x if x.is_ascii_digit() => {
-                    let mut int_last = first;
-                    while let Some((offset, _)) =
-                        Self::check_next(&mut char_indices, Box::new(|c| c.is_ascii_digit()))
-                    {
-                        int_last = offset;
+                    let token = self.pop_token(Identifier, first, last);
+                    match keyword(token.lexeme()) {
+                        Some(typ) => tokens.push(self.pop_token(typ, first, last)),
+                        None => tokens.push(token),
                     }
-                    if let Some((offset, _)) = Self::check_next(&mut char_indices, Self::is('.')) {
-                        if Self::check_next(&mut char_indices, Box::new(|c| c.is_ascii_digit()))
+                }
+                x if is_digit(x) => {
+                    let mut last = first;
+                    if matches!(x, '0' | '\u{0660}')
+                        && Self::check_next(&mut char_indices, Box::new(|c| radix(c).is_some()))
                             .is_some()
+                    {
+                        // Everything that could continue an identifier is consumed so that `0xفغ` is reported as a whole.
+                        last = first + x.len_utf8();
+                        while let Some((offset, _)) = Self::check_next(
+                            &mut char_indices,
+                            Box::new(|c| c.is_alphanumeric() || c == '_'),
+                        ) {
+                            last = offset;
+                        }
+                    } else {
+                        while let Some((offset, _)) = Self::check_next(
+                            &mut char_indices,
+                            Box::new(|c| is_digit(c) || c == '_'),
+                        ) {
+                            last = offset;
+                        }
+                        // The fraction is only consumed when the separator is followed by a digit, so `1.` stays a number followed by a period.
+                        let mut lookahead = char_indices.clone();
+                        if matches!(Self::next(&mut lookahead), Some((_, c)) if is_decimal_separator(c))
+                            && Self::check(&mut lookahead, Box::new(is_digit))
                         {
-                            todo!("Floats")
-                        } else {
-                            tokens.push(self.pop_token(Number, first, int_last));
-                            tokens.push(self.pop_token(Period, offset, offset));
+                            Self::next(&mut char_indices);
+                            while let Some((offset, _)) = Self::check_next(
+                                &mut char_indices,
+                                Box::new(|c| is_digit(c) || c == '_'),
+                            ) {
+                                last = offset;
+                            }
                         }
+                    }
+                    let token = self.pop_token(Number, first, last);
+                    if is_valid_number(token.lexeme()) {
+                        tokens.push(token);
                     } else {
-                        tokens.push(self.pop_token(Number, first, int_last));
+                        tokens.push(self.pop_token(MalformedNumber, first, last));
                     }
                 }
                 _ => single!(Unknown),
@@ -255,7 +482,7 @@ impl Lexer {
             EOF,
             Rc::clone(&source),
             self.path.as_ref(),
-            source.len() - 1,
+            source.len(),
             0,
         )));
         tokens
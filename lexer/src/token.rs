@@ -1,7 +1,7 @@
 extern crate variant_count;
 
 use colored::Colorize;
-use std::{cmp::PartialEq, convert::Into, fmt, path::PathBuf, rc::Rc, string};
+use std::{cmp::PartialEq, fmt, path::PathBuf, rc::Rc, string};
 use variant_count::VariantCount;
 
 use super::is_newline;
@@ -15,135 +15,173 @@ pub enum TokenType {
     OBracket,                 // 4
     CBracket,                 // 5
     Period,                   // 6
-    TPeriod,                  // 7
-    Plus,                     // 8
-    Minus,                    // 9
-    Star,                     // 10
-    Slash,                    // 11
-    Percent,                  // 12
-    Comma,                    // 13
-    QuestionMark,             // 14
-    Colon,                    // 15
-    Equal,                    // 16
-    PlusEqual,                // 17
-    MinusEqual,               // 18
-    StarEqual,                // 19
-    SlashEqual,               // 20
-    PercentEqual,             // 21
-    DEqual,                   // 22
-    Bang,                     // 23
-    BangEqual,                // 24
-    Greater,                  // 25
-    GreaterEqual,             // 26
-    Less,                     // 27
-    LessEqual,                // 28
-    And,                      // 29
-    Or,                       // 30
-    String,                   // 31
-    UnterminatedString,       // 32
-    InlineComment,            // 33
-    BlockComment,             // 34
-    UnterminatedBlockComment, // 35
-    Identifier,               // 36
-    If,                       // 37
-    ElseIf,                   // 38
-    Else,                     // 39
-    Function,                 // 38
-    Var,                      // 39
-    Loop,                     // 40
-    While,                    // 41
-    Break,                    // 42
-    Continue,                 // 43
-    Return,                   // 44
-    Throw,                    // 45
-    Try,                      // 46
-    Catch,                    // 47
-    Nil,                      // 48
-    True,                     // 49
-    False,                    // 50
-    Number,                   // 51
-    Import,                   // 52
-    From,                     // 53
-    Export,                   // 54
-    Pipe,                     // 55
-    For,                      // 56
-    In,                       // 57
-    Unknown,                  // 58
-    NewLine,                  // 59
-    EOF,                      // 60
+    DPeriod,                  // 7
+    TPeriod,                  // 8
+    Plus,                     // 9
+    Minus,                    // 10
+    Star,                     // 11
+    Slash,                    // 12
+    Percent,                  // 13
+    Comma,                    // 14
+    QuestionMark,             // 15
+    QuestionPeriod,           // 16
+    DQuestionMark,            // 17
+    Colon,                    // 18
+    Equal,                    // 19
+    PlusEqual,                // 20
+    MinusEqual,               // 21
+    StarEqual,                // 22
+    SlashEqual,               // 23
+    PercentEqual,             // 24
+    DEqual,                   // 25
+    Bang,                     // 26
+    BangEqual,                // 27
+    Greater,                  // 28
+    GreaterEqual,             // 29
+    Less,                     // 30
+    LessEqual,                // 31
+    And,                      // 32
+    Or,                       // 33
+    DStar,                    // 34
+    BackSlash,                // 35
+    Ampersand,                // 36
+    Caret,                    // 37
+    Tilde,                    // 38
+    DLess,                    // 39
+    DGreater,                 // 40
+    String,                   // 41
+    UnterminatedString,       // 42
+    Interpolation,            // 43
+    InlineComment,            // 44
+    BlockComment,             // 45
+    UnterminatedBlockComment, // 46
+    Identifier,               // 47
+    If,                       // 48
+    ElseIf,                   // 49
+    Else,                     // 50
+    Function,                 // 51
+    Var,                      // 52
+    Loop,                     // 53
+    While,                    // 54
+    Break,                    // 55
+    Continue,                 // 56
+    Return,                   // 57
+    Throw,                    // 58
+    Try,                      // 59
+    Catch,                    // 60
+    Nil,                      // 61
+    True,                     // 62
+    False,                    // 63
+    Number,                   // 64
+    MalformedNumber,          // 65
+    Import,                   // 66
+    From,                     // 67
+    Export,                   // 68
+    Pipe,                     // 69
+    For,                      // 70
+    In,                       // 71
+    Match,                    // 72
+    Class,                    // 73
+    This,                     // 74
+    Super,                    // 75
+    Yield,                    // 76
+    Finally,                  // 77
+    Const,                    // 78
+    Unknown,                  // 79
+    NewLine,                  // 80
+    EOF,                      // 81
 }
 
-impl Into<&'static str> for TokenType {
-    fn into(self) -> &'static str {
-        match self {
-            Self::NewLine => "سطر جديد",
-            Self::OParen => "(",
-            Self::CParen => ")",
-            Self::OBrace => "{",
-            Self::CBrace => "}",
-            Self::OBracket => "]",
-            Self::CBracket => "[",
-            Self::Period => ".",
-            Self::TPeriod => "...",
-            Self::Plus => "+",
-            Self::Minus => "-",
-            Self::Star => "*",
-            Self::Slash => "/",
-            Self::Percent => "%",
-            Self::Comma => "،",
-            Self::QuestionMark => "؟",
-            Self::Colon => ":",
+impl From<TokenType> for &'static str {
+    fn from(typ: TokenType) -> Self {
+        match typ {
+            TokenType::NewLine => "سطر جديد",
+            TokenType::OParen => "(",
+            TokenType::CParen => ")",
+            TokenType::OBrace => "{",
+            TokenType::CBrace => "}",
+            TokenType::OBracket => "]",
+            TokenType::CBracket => "[",
+            TokenType::Period => ".",
+            TokenType::DPeriod => "..",
+            TokenType::TPeriod => "...",
+            TokenType::Plus => "+",
+            TokenType::Minus => "-",
+            TokenType::Star => "*",
+            TokenType::Slash => "/",
+            TokenType::Percent => "%",
+            TokenType::Comma => "،",
+            TokenType::QuestionMark => "؟",
+            TokenType::QuestionPeriod => "؟.",
+            TokenType::DQuestionMark => "؟؟",
+            TokenType::Colon => ":",
 
-            Self::Equal => "=",
-            Self::PlusEqual => "+=",
-            Self::MinusEqual => "-=",
-            Self::StarEqual => "*=",
-            Self::SlashEqual => "/=",
-            Self::PercentEqual => "%=",
+            TokenType::Equal => "=",
+            TokenType::PlusEqual => "+=",
+            TokenType::MinusEqual => "-=",
+            TokenType::StarEqual => "*=",
+            TokenType::SlashEqual => "/=",
+            TokenType::PercentEqual => "%=",
 
-            Self::DEqual => "==",
-            Self::Bang => "!",
-            Self::BangEqual => "!=",
-            Self::Greater => ">",
-            Self::GreaterEqual => ">=",
-            Self::Less => "<",
-            Self::LessEqual => "<=",
-            Self::And => "&&",
-            Self::Or => "||",
+            TokenType::DEqual => "==",
+            TokenType::Bang => "!",
+            TokenType::BangEqual => "!=",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::And => "و",
+            TokenType::Or => "أو",
+            TokenType::DStar => "**",
+            TokenType::BackSlash => "\\",
+            TokenType::Ampersand => "&",
+            TokenType::Caret => "^",
+            TokenType::Tilde => "~",
+            TokenType::DLess => "<<",
+            TokenType::DGreater => ">>",
 
-            Self::String => "نص",
-            Self::UnterminatedString => "نص غير مغلق",
-            Self::InlineComment => "تعليق سطري",
-            Self::BlockComment => "تعليق",
-            Self::UnterminatedBlockComment => "تعليق غير مغلق",
+            TokenType::String => "نص",
+            TokenType::UnterminatedString => "نص غير مغلق",
+            TokenType::Interpolation => "جزء من نص",
+            TokenType::InlineComment => "تعليق سطري",
+            TokenType::BlockComment => "تعليق",
+            TokenType::UnterminatedBlockComment => "تعليق غير مغلق",
 
-            Self::Identifier => "كلمة",
-            Self::If => "إن",
-            Self::ElseIf => "وإن",
-            Self::Else => "إلا",
-            Self::Function => "دالة",
-            Self::Var => "متغير",
-            Self::Loop => "كرر",
-            Self::While => "طالما",
-            Self::Break => "إكسر",
-            Self::Continue => "واصل",
-            Self::Return => "أرجع",
-            Self::Throw => "ألقي",
-            Self::Try => "حاول",
-            Self::Catch => "أمسك",
-            Self::Nil => "عدم",
-            Self::True => "صحيح",
-            Self::False => "خطأ",
-            Self::Number => "رقم",
+            TokenType::Identifier => "كلمة",
+            TokenType::If => "إن",
+            TokenType::ElseIf => "وإن",
+            TokenType::Else => "إلا",
+            TokenType::Function => "دالة",
+            TokenType::Var => "متغير",
+            TokenType::Loop => "كرر",
+            TokenType::While => "طالما",
+            TokenType::Break => "إكسر",
+            TokenType::Continue => "واصل",
+            TokenType::Return => "أرجع",
+            TokenType::Throw => "ألقي",
+            TokenType::Try => "حاول",
+            TokenType::Catch => "أمسك",
+            TokenType::Nil => "عدم",
+            TokenType::True => "صحيح",
+            TokenType::False => "خطأ",
+            TokenType::Number => "رقم",
+            TokenType::MalformedNumber => "رقم غير صالح",
 
-            Self::Import => "استورد",
-            Self::From => "من",
-            Self::Export => "صدّر",
-            Self::Pipe => "|",
-            Self::For => "لكل",
-            Self::In => "في",
-            Self::Unknown => "حرف غير معروف",
-            Self::EOF => "النهاية",
+            TokenType::Import => "استورد",
+            TokenType::From => "من",
+            TokenType::Export => "صدّر",
+            TokenType::Pipe => "|",
+            TokenType::For => "لكل",
+            TokenType::In => "في",
+            TokenType::Match => "طابق",
+            TokenType::Class => "صنف",
+            TokenType::This => "هذا",
+            TokenType::Super => "أصل",
+            TokenType::Yield => "أنتج",
+            TokenType::Finally => "أخيراً",
+            TokenType::Const => "ثابت",
+            TokenType::Unknown => "حرف غير معروف",
+            TokenType::EOF => "النهاية",
         }
     }
 }
@@ -159,10 +197,7 @@ impl Token {
         Self {
             typ,
             source,
-            path: match path {
-                Some(path) => Some(path.clone()),
-                None => None,
-            },
+            path: path.cloned(),
             start,
             length,
         }
@@ -185,12 +220,12 @@ impl Token {
     pub fn line(&self) -> usize {
         let mut line = 1;
         for (offset, c) in self.source.char_indices() {
-            if is_newline(c) {
-                line += 1;
-            }
             if offset == self.start {
                 break;
             }
+            if is_newline(c) {
+                line += 1;
+            }
         }
         line
     }
@@ -221,10 +256,6 @@ impl fmt::Display for Token {
         let mut line_idx = 0;
         let mut line_start_offset = 0;
         while let Some((offset, c)) = char_indices.next() {
-            if is_newline(c) {
-                line_idx += 1;
-                line_start_offset = offset;
-            }
             if offset == self.start {
                 while let Some((offset, _)) = char_indices.peek() {
                     if *offset == self.start + self.length {
@@ -235,6 +266,10 @@ impl fmt::Display for Token {
                 }
                 break;
             }
+            if is_newline(c) {
+                line_idx += 1;
+                line_start_offset = offset + c.len_utf8();
+            }
         }
         let line = line_idx + 1;
         let indent = (line_idx + 1).to_string().len();
@@ -247,7 +282,7 @@ impl fmt::Display for Token {
                 path.display().to_string().bright_cyan()
             )?
         }
-        writeln!(f, "{:indent$} {}", "", "|".bright_cyan())?;
+        writeln!(f, "{:indent$} {} ", "", "|".bright_cyan())?;
         write!(
             f,
             "{} {} ",
@@ -258,17 +293,20 @@ impl fmt::Display for Token {
             f,
             "{}{}",
             self.source.get(line_start_offset..self.start).unwrap(),
-            self.lexeme().underline().bold()
+            self.lexeme()
+                .trim_end_matches(is_newline)
+                .underline()
+                .bold()
         )?;
-        while let Some((_, c)) = char_indices.next() {
+        for (_, c) in char_indices {
             if is_newline(c) {
                 break;
             } else {
                 write!(f, "{c}")?
             }
         }
-        write!(f, "\n")?;
-        writeln!(f, "{:indent$} {}", "", "|".bright_cyan())?;
+        writeln!(f)?;
+        write!(f, "{:indent$} {} ", "", "|".bright_cyan())?;
         Ok(())
     }
 }
@@ -289,10 +327,11 @@ pub trait TokenInside {
     fn token(&self) -> Rc<Token>;
 }
 
-pub const ERROR_TOKENS: [TokenType; 3] = [
+pub const ERROR_TOKENS: [TokenType; 4] = [
     TokenType::Unknown,
     TokenType::UnterminatedString,
     TokenType::UnterminatedBlockComment,
+    TokenType::MalformedNumber,
 ];
 
 pub const BINARY_SET: [TokenType; 6] = [
@@ -13,7 +13,7 @@ pub enum Literal {
     Nil(Rc<Token>),
     /// token, exprs
     List(Rc<Token>, Vec<Expr>),
-    /// token, props: \[(key, value, default)\]
+    /// token, props: \[(key, value, default)\], spreads are stored as `(op, Some(Expr::Spread(..)), None)`
     Object(
         Rc<Token>,
         Vec<(Rc<Token>, Option<Expr>, Option<(Rc<Token>, Expr)>)>,
@@ -56,6 +56,18 @@ pub enum Expr {
     Call(Box<Expr>, Rc<Token>, Vec<Expr>),
     /// expr, op, key
     Member(Box<Expr>, Rc<Token>, Box<Expr>),
+    /// token
+    This(Rc<Token>),
+    /// token, key
+    Super(Rc<Token>, Rc<Token>),
+    /// op, expr: only found in the elements of lists, the props of objects, and the arguments of calls
+    Spread(Rc<Token>, Box<Expr>),
+    /// start, op, end, step
+    Range(Box<Expr>, Rc<Token>, Box<Expr>, Option<Box<Expr>>),
+    /// condition, op, then, else_
+    Ternary(Box<Expr>, Rc<Token>, Box<Expr>, Box<Expr>),
+    /// strings, exprs: the strings surround the exprs, so there's always one more string than exprs
+    Interpolation(Vec<Rc<Token>>, Vec<Expr>),
 }
 
 impl From<Literal> for Expr {
@@ -67,12 +79,16 @@ impl From<Literal> for Expr {
 impl TokenInside for Expr {
     fn token(&self) -> Rc<Token> {
         match self {
-            Self::Variable(token) => Rc::clone(token),
+            Self::Variable(token) | Self::This(token) | Self::Super(token, ..) => Rc::clone(token),
             Self::Unary(op, ..)
             | Self::Binary(_, op, ..)
             | Self::Call(_, op, ..)
-            | Self::Member(_, op, ..) => Rc::clone(op),
+            | Self::Member(_, op, ..)
+            | Self::Ternary(_, op, ..)
+            | Self::Range(_, op, ..)
+            | Self::Spread(op, ..) => Rc::clone(op),
             Self::Literal(literal) => literal.token(),
+            Self::Interpolation(strings, _) => Rc::clone(&strings[0]),
         }
     }
 }
@@ -91,14 +107,21 @@ pub enum Stml {
         Option<(Rc<Token>, Box<Expr>)>,
         Box<Stml>,
     ),
-    /// export_token, token, decls: \[(definable, init)\]
+    /// export_token, token: `متغير` or `ثابت`, decls: \[(definable, init)\]
     VarDecl(Option<Rc<Token>>, Rc<Token>, Vec<(Expr, Option<Expr>)>),
     /// token, expr
     Return(Rc<Token>, Option<Expr>),
     /// token, expr
     Throw(Rc<Token>, Option<Expr>),
-    /// token, body, catch_token, err, catch_body
-    TryCatch(Rc<Token>, Box<Stml>, Rc<Token>, Rc<Token>, Box<Stml>),
+    /// token, expr
+    Yield(Rc<Token>, Option<Expr>),
+    /// token, body, catches: \[(catch_token, err, kinds, catch_body)\], finally: (finally_token, finally_body)
+    TryCatch(
+        Rc<Token>,
+        Box<Stml>,
+        Vec<(Rc<Token>, Rc<Token>, Vec<Rc<Token>>, Stml)>,
+        Option<(Rc<Token>, Box<Stml>)>,
+    ),
     /// token, condition, body, elseifs: \[(token, condition, body)\], else_: (token, body)
     If(
         Rc<Token>,
@@ -107,18 +130,40 @@ pub enum Stml {
         Vec<(Rc<Token>, Expr, Stml)>,
         Option<(Rc<Token>, Box<Stml>)>,
     ),
-    /// token, condition, body
-    While(Rc<Token>, Expr, Box<Stml>),
-    /// token, body
-    Loop(Rc<Token>, Box<Stml>),
-    /// token
-    Break(Rc<Token>),
-    /// token
-    Continue(Rc<Token>),
+    /// label, token, condition, body
+    While(Option<Rc<Token>>, Rc<Token>, Expr, Box<Stml>),
+    /// label, token, body
+    Loop(Option<Rc<Token>>, Rc<Token>, Box<Stml>),
+    /// token, label
+    Break(Rc<Token>, Option<Rc<Token>>),
+    /// token, label
+    Continue(Rc<Token>, Option<Rc<Token>>),
     /// token, definable, from_token, path
     Import(Rc<Token>, Expr, Rc<Token>, Rc<Token>),
-    /// token, definable, in_token, iterable, body
-    ForIn(Rc<Token>, Expr, Rc<Token>, Expr, Box<Stml>),
+    /// label, token, definable, in_token, iterable, body
+    ForIn(
+        Option<Rc<Token>>,
+        Rc<Token>,
+        Expr,
+        Rc<Token>,
+        Expr,
+        Box<Stml>,
+    ),
+    /// export_token, token, name, parent: (from_token, parent), methods: \[FunctionDecl\]
+    ClassDecl(
+        Option<Rc<Token>>,
+        Rc<Token>,
+        Rc<Token>,
+        Option<(Rc<Token>, Expr)>,
+        Vec<Stml>,
+    ),
+    /// token, subject, arms: \[(pattern, guard, body)\], default: (token, body)
+    Match(
+        Rc<Token>,
+        Expr,
+        Vec<(Expr, Option<Expr>, Stml)>,
+        Option<(Rc<Token>, Box<Stml>)>,
+    ),
     /// expr
     Expr(Expr),
 }
@@ -128,17 +173,20 @@ impl TokenInside for Stml {
         match self {
             Self::Block(token, ..)
             | Self::FunctionDecl(_, token, ..)
+            | Self::ClassDecl(_, token, ..)
             | Self::VarDecl(_, token, ..)
             | Self::Return(token, ..)
             | Self::Throw(token, ..)
+            | Self::Yield(token, ..)
             | Self::TryCatch(token, ..)
             | Self::If(token, ..)
-            | Self::While(token, ..)
-            | Self::Loop(token, ..)
-            | Self::Break(token)
-            | Self::Continue(token)
+            | Self::While(_, token, ..)
+            | Self::Loop(_, token, ..)
+            | Self::Break(token, ..)
+            | Self::Continue(token, ..)
             | Self::Import(token, ..)
-            | Self::ForIn(token, ..) => Rc::clone(token),
+            | Self::ForIn(_, token, ..)
+            | Self::Match(token, ..) => Rc::clone(token),
             Self::Expr(expr) => expr.token(),
         }
     }
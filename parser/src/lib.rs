@@ -1,3 +1,5 @@
+#![allow(clippy::type_complexity)]
+
 pub mod ast;
 pub mod error;
 mod operators;
@@ -25,7 +27,12 @@ pub struct Parser {
 impl Parser {
     pub fn new(tokens: Vec<Rc<Token>>) -> Self {
         Self {
-            tokens,
+            tokens: tokens
+                .into_iter()
+                .filter(|token| {
+                    ![TokenType::InlineComment, TokenType::BlockComment].contains(&token.typ())
+                })
+                .collect(),
             current: 0,
             errors: vec![],
         }
@@ -94,9 +101,11 @@ impl Parser {
         }
     }
 
-    /// Advance `self.current`.
+    /// Advance `self.current`, it stays at EOF once it reaches it.
     fn advance(&mut self) -> Result<(), ()> {
-        self.current += 1;
+        if self.peek().typ() != TokenType::EOF {
+            self.current += 1;
+        }
         self.validate_current()
     }
 
@@ -131,6 +140,15 @@ impl Parser {
 
         expr = match token.typ() {
             TokenType::Identifier => Expr::Variable(Rc::clone(&token)),
+            TokenType::This => {
+                assign_abililty = AssignAbility::None;
+                Expr::This(Rc::clone(&token))
+            }
+            TokenType::Super => {
+                assign_abililty = AssignAbility::None;
+                self.consume(&[TokenType::Period])?;
+                Expr::Super(Rc::clone(&token), self.consume(&[TokenType::Identifier])?)
+            }
             TokenType::OBracket | TokenType::OBrace => {
                 assign_abililty = AssignAbility::OnlyEqual;
                 self.literal()?
@@ -144,15 +162,18 @@ impl Parser {
                 assign_abililty = AssignAbility::None;
                 self.literal()?
             }
-            TokenType::Minus | TokenType::Bang => {
+            TokenType::Minus | TokenType::Bang | TokenType::Tilde => {
                 assign_abililty = AssignAbility::None;
-                self.literal()?
+                self.unary()?
             }
             TokenType::OParen => {
                 assign_abililty = AssignAbility::None;
-                self.literal()?
+                self.group()?
+            }
+            TokenType::Interpolation => {
+                assign_abililty = AssignAbility::None;
+                self.interpolation()?
             }
-            TokenType::EOF => return Err(()),
             _ => {
                 self.parse_err(ParseError::ExpectedExpr(token));
                 return Err(());
@@ -171,6 +192,25 @@ impl Parser {
                 if !BINARY_SET.contains(&op.typ()) {
                     assign_abililty = AssignAbility::None;
                 }
+                if op.typ() == TokenType::QuestionMark {
+                    let then = self.parse_expr()?;
+                    self.consume(&[TokenType::Colon])?;
+                    let else_ = self.expr(infix_precedence, AssignAbility::None)?;
+                    expr = Expr::Ternary(Box::new(expr), op, Box::new(then), Box::new(else_));
+                    continue;
+                }
+                if op.typ() == TokenType::DPeriod {
+                    let end = self.expr(infix_precedence - 1, AssignAbility::None)?;
+                    let step = if self.check_consume(&[TokenType::DPeriod])? {
+                        Some(Box::new(
+                            self.expr(infix_precedence - 1, AssignAbility::None)?,
+                        ))
+                    } else {
+                        None
+                    };
+                    expr = Expr::Range(Box::new(expr), op, Box::new(end), step);
+                    continue;
+                }
                 let can_assign = Self::can_assign(op.typ(), assign_abililty);
                 if BINARY_SET.contains(&op.typ()) && !can_assign {
                     self.parse_err(ParseError::InvalidRhs(Rc::clone(&op)));
@@ -190,7 +230,7 @@ impl Parser {
                         },
                     )?),
                 );
-            } else if let Some(postfix_precedence) = OPERATORS[row as usize].2 {
+            } else if let Some(postfix_precedence) = OPERATORS[row].2 {
                 if min_precedence < postfix_precedence {
                     break;
                 }
@@ -201,11 +241,8 @@ impl Parser {
                         expr = Expr::Call(Box::new(expr), op, self.exprs(TokenType::CParen)?);
                     }
                     TokenType::Period | TokenType::OBracket => {
-                        match expr {
-                            Expr::Call(_, _, _) => {
-                                assign_abililty = AssignAbility::AnyOp;
-                            }
-                            _ => {}
+                        if let Expr::Call(..) | Expr::This(..) = expr {
+                            assign_abililty = AssignAbility::AnyOp;
                         }
                         let key = match op.typ() {
                             TokenType::Period => {
@@ -222,6 +259,19 @@ impl Parser {
 
                         expr = Expr::Member(Box::new(expr), op, Box::new(key));
                     }
+                    // Optional members can't be assigned to, and they're written either as `؟.key` or as `؟.[key]`.
+                    TokenType::QuestionPeriod => {
+                        assign_abililty = AssignAbility::None;
+                        let key = if self.check_consume(&[TokenType::OBracket])? {
+                            let tmp = self.parse_expr()?;
+                            self.consume(&[TokenType::CBracket])?;
+                            tmp
+                        } else {
+                            self.consume(&[TokenType::Identifier])?;
+                            Expr::Literal(Literal::String(self.previous()))
+                        };
+                        expr = Expr::Member(Box::new(expr), op, Box::new(key));
+                    }
                     _ => unreachable!(),
                 }
             } else {
@@ -231,6 +281,46 @@ impl Parser {
         Ok(expr)
     }
 
+    fn unary(&mut self) -> Result<Expr, ()> {
+        let op = self.previous();
+        let prefix_precedence = OPERATORS[op.typ() as usize].0.unwrap();
+        let expr = self.expr(prefix_precedence, AssignAbility::None)?;
+        Ok(Expr::Unary(op, Box::new(expr)))
+    }
+
+    fn group(&mut self) -> Result<Expr, ()> {
+        let expr = self.parse_expr()?;
+        self.consume(&[TokenType::CParen])?;
+        Ok(expr)
+    }
+
+    /// Parses the exprs of an interpolated string along with the strings surrounding them.
+    fn interpolation(&mut self) -> Result<Expr, ()> {
+        let mut strings = vec![self.previous()];
+        let mut exprs = vec![];
+        loop {
+            // An empty interpolation leaves the rest of the string in place of the expr.
+            let token = self.peek();
+            if [TokenType::String, TokenType::Interpolation].contains(&token.typ())
+                && token.lexeme().starts_with('}')
+            {
+                self.parse_err(ParseError::ExpectedExpr(token));
+                return Err(());
+            }
+            exprs.push(self.parse_expr()?);
+            let string = self.next()?;
+            if ![TokenType::String, TokenType::Interpolation].contains(&string.typ()) {
+                self.parse_err(ParseError::ExpectedInstead(vec![TokenType::CBrace], string));
+                return Err(());
+            }
+            strings.push(Rc::clone(&string));
+            if string.typ() == TokenType::String {
+                break;
+            }
+        }
+        Ok(Expr::Interpolation(strings, exprs))
+    }
+
     fn literal(&mut self) -> Result<Expr, ()> {
         let token = self.previous();
         match token.typ() {
@@ -245,15 +335,25 @@ impl Parser {
         }
     }
 
+    /// Parses an expression that can be spread with `...`.
+    fn spreadable(&mut self) -> Result<Expr, ()> {
+        if self.check_consume(&[TokenType::TPeriod])? {
+            let op = self.previous();
+            Ok(Expr::Spread(op, Box::new(self.parse_expr()?)))
+        } else {
+            self.parse_expr()
+        }
+    }
+
     fn exprs(&mut self, closing_token: TokenType) -> Result<Vec<Expr>, ()> {
         let mut exprs = vec![];
         if !self.check(&[closing_token])? {
-            exprs.push(self.parse_expr()?);
+            exprs.push(self.spreadable()?);
             while self.check_consume(&[TokenType::Comma])? {
                 if self.check(&[closing_token])? {
                     break;
                 }
-                exprs.push(self.parse_expr()?)
+                exprs.push(self.spreadable()?)
             }
         }
         self.consume(&[closing_token])?;
@@ -266,6 +366,10 @@ impl Parser {
     }
 
     fn prop(&mut self) -> Result<(Rc<Token>, Option<Expr>, Option<(Rc<Token>, Expr)>), ()> {
+        if self.check(&[TokenType::TPeriod])? {
+            let spread = self.spreadable()?;
+            return Ok((spread.token(), Some(spread), None));
+        }
         self.consume(&[TokenType::Identifier])?;
         let key = self.previous();
         let mut value = if self.check_consume(&[TokenType::Colon])? {
@@ -311,15 +415,83 @@ impl Parser {
     }
 
     fn lambda(&mut self) -> Result<Literal, ()> {
-        todo!()
+        let token = self.previous();
+        let (required, optional, variadic) = self.params(TokenType::Pipe)?;
+        let body = self.block()?;
+        Ok(Literal::Lambda(
+            token,
+            required,
+            optional,
+            variadic,
+            Box::new(body),
+        ))
     }
 
     fn parse_expr(&mut self) -> Result<Expr, ()> {
-        self.expr(9, AssignAbility::AnyOp)
+        self.expr(16, AssignAbility::AnyOp)
     }
 
+    /// Parses an identifier, a list, or an object, leaving checking whether it can be destructured to the compiler.
     fn definable(&mut self) -> Result<Expr, ()> {
-        todo!()
+        let token = self.consume(&[
+            TokenType::Identifier,
+            TokenType::OBracket,
+            TokenType::OBrace,
+        ])?;
+        match token.typ() {
+            TokenType::Identifier => Ok(Expr::Variable(token)),
+            TokenType::OBracket => Ok(self.list()?.into()),
+            TokenType::OBrace => Ok(self.object()?.into()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Parses the parameters of a function until `closing_token` which is consumed.
+    fn params(
+        &mut self,
+        closing_token: TokenType,
+    ) -> Result<(Vec<Expr>, Vec<(Expr, Expr)>, Option<(Rc<Token>, Box<Expr>)>), ()> {
+        let mut required = vec![];
+        let mut optional = vec![];
+        let mut variadic = None;
+        if !self.check(&[closing_token])? {
+            loop {
+                if self.check_consume(&[TokenType::TPeriod])? {
+                    let token = self.previous();
+                    variadic = Some((token, Box::new(self.definable()?)));
+                    break;
+                }
+                let definable = self.definable()?;
+                if self.check_consume(&[TokenType::Equal])? {
+                    let default = match closing_token {
+                        // Stops before `|` so that it closes the params instead of being parsed as a bitwise or.
+                        TokenType::Pipe => self.expr(9, AssignAbility::None)?,
+                        _ => self.parse_expr()?,
+                    };
+                    optional.push((definable, default))
+                } else if !optional.is_empty() {
+                    self.parse_err(ParseError::ExpectedOptional(definable.token()));
+                    return Err(());
+                } else {
+                    required.push(definable)
+                }
+                if !self.check_consume(&[TokenType::Comma])? || self.check(&[closing_token])? {
+                    break;
+                }
+            }
+        }
+        self.consume(&[closing_token])?;
+        Ok((required, optional, variadic))
+    }
+
+    fn block(&mut self) -> Result<Stml, ()> {
+        let token = self.consume(&[TokenType::OBrace])?;
+        let mut stmls = vec![];
+        while !self.check(&[TokenType::CBrace])? && !self.at_end()? {
+            stmls.push(self.stml()?);
+        }
+        self.consume(&[TokenType::CBrace])?;
+        Ok(Stml::Block(token, stmls))
     }
 
     fn import_stml(&mut self) -> Result<Stml, ()> {
@@ -330,6 +502,229 @@ impl Parser {
         Ok(Stml::Import(token, definable, from_token, path))
     }
 
+    fn function_decl(&mut self, export_token: Option<Rc<Token>>) -> Result<Stml, ()> {
+        let token = self.previous();
+        let name = self.consume(&[TokenType::Identifier])?;
+        self.consume(&[TokenType::OParen])?;
+        let (required, optional, variadic) = self.params(TokenType::CParen)?;
+        let body = self.block()?;
+        Ok(Stml::FunctionDecl(
+            export_token,
+            token,
+            name,
+            required,
+            optional,
+            variadic,
+            Box::new(body),
+        ))
+    }
+
+    fn class_decl(&mut self, export_token: Option<Rc<Token>>) -> Result<Stml, ()> {
+        let token = self.previous();
+        let name = self.consume(&[TokenType::Identifier])?;
+        let parent = if self.check_consume(&[TokenType::From])? {
+            let from_token = self.previous();
+            Some((from_token, self.parse_expr()?))
+        } else {
+            None
+        };
+        self.consume(&[TokenType::OBrace])?;
+        let mut methods = vec![];
+        while !self.check(&[TokenType::CBrace])? && !self.at_end()? {
+            self.consume(&[TokenType::Function])?;
+            methods.push(self.function_decl(None)?);
+        }
+        self.consume(&[TokenType::CBrace])?;
+        Ok(Stml::ClassDecl(export_token, token, name, parent, methods))
+    }
+
+    fn var_decl(&mut self, export_token: Option<Rc<Token>>) -> Result<Stml, ()> {
+        let token = self.previous();
+        let mut decls = vec![];
+        loop {
+            let definable = self.definable()?;
+            // Constants must be initialized.
+            let init = if token.typ() == TokenType::Const {
+                self.consume(&[TokenType::Equal])?;
+                Some(self.parse_expr()?)
+            } else if self.check_consume(&[TokenType::Equal])? {
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+            decls.push((definable, init));
+            if !self.check_consume(&[TokenType::Comma])? {
+                break;
+            }
+        }
+        Ok(Stml::VarDecl(export_token, token, decls))
+    }
+
+    fn export_stml(&mut self) -> Result<Stml, ()> {
+        let token = self.previous();
+        let typ = self
+            .consume(&[
+                TokenType::Function,
+                TokenType::Class,
+                TokenType::Var,
+                TokenType::Const,
+            ])?
+            .typ();
+        match typ {
+            TokenType::Function => self.function_decl(Some(token)),
+            TokenType::Class => self.class_decl(Some(token)),
+            TokenType::Var | TokenType::Const => self.var_decl(Some(token)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Checks if the next tokens are a label (e.g. `خارجية:`) followed by a loop.
+    fn check_label(&mut self) -> Result<bool, ()> {
+        Ok(self.check(&[TokenType::Identifier])?
+            && self
+                .tokens
+                .get(self.current + 1)
+                .is_some_and(|token| token.typ() == TokenType::Colon))
+    }
+
+    fn labeled_stml(&mut self) -> Result<Stml, ()> {
+        let label = self.consume(&[TokenType::Identifier])?;
+        self.consume(&[TokenType::Colon])?;
+        let typ = self
+            .consume(&[TokenType::While, TokenType::Loop, TokenType::For])?
+            .typ();
+        match typ {
+            TokenType::While => self.while_stml(Some(label)),
+            TokenType::Loop => self.loop_stml(Some(label)),
+            TokenType::For => self.for_in_stml(Some(label)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Parses the optional label of `إكسر` and `واصل` which must be on the same line.
+    fn optional_label(&mut self) -> Result<Option<Rc<Token>>, ()> {
+        if self.peek().typ() == TokenType::Identifier {
+            Ok(Some(self.next()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn while_stml(&mut self, label: Option<Rc<Token>>) -> Result<Stml, ()> {
+        let token = self.previous();
+        let condition = self.parse_expr()?;
+        let body = self.block()?;
+        Ok(Stml::While(label, token, condition, Box::new(body)))
+    }
+
+    fn loop_stml(&mut self, label: Option<Rc<Token>>) -> Result<Stml, ()> {
+        let token = self.previous();
+        let body = self.block()?;
+        Ok(Stml::Loop(label, token, Box::new(body)))
+    }
+
+    fn if_stml(&mut self) -> Result<Stml, ()> {
+        let token = self.previous();
+        let condition = self.parse_expr()?;
+        let body = self.block()?;
+        let mut elseifs = vec![];
+        while self.check_consume(&[TokenType::ElseIf])? {
+            let token = self.previous();
+            let condition = self.parse_expr()?;
+            elseifs.push((token, condition, self.block()?));
+        }
+        let else_ = if self.check_consume(&[TokenType::Else])? {
+            let token = self.previous();
+            Some((token, Box::new(self.block()?)))
+        } else {
+            None
+        };
+        Ok(Stml::If(token, condition, Box::new(body), elseifs, else_))
+    }
+
+    fn try_catch_stml(&mut self) -> Result<Stml, ()> {
+        let token = self.previous();
+        let body = self.block()?;
+        let mut catches = vec![];
+        while self.check_consume(&[TokenType::Catch])? {
+            let catch_token = self.previous();
+            self.consume(&[TokenType::OParen])?;
+            let err = self.consume(&[TokenType::Identifier])?;
+            let mut kinds = vec![];
+            if self.check_consume(&[TokenType::Colon])? {
+                kinds.push(self.consume(&[TokenType::Identifier])?);
+                while self.check_consume(&[TokenType::Comma])? {
+                    kinds.push(self.consume(&[TokenType::Identifier])?);
+                }
+            }
+            self.consume(&[TokenType::CParen])?;
+            catches.push((catch_token, err, kinds, self.block()?));
+        }
+        let finally = if catches.is_empty() {
+            let finally_token = self.consume(&[TokenType::Catch, TokenType::Finally])?;
+            Some((finally_token, Box::new(self.block()?)))
+        } else if self.check_consume(&[TokenType::Finally])? {
+            let finally_token = self.previous();
+            Some((finally_token, Box::new(self.block()?)))
+        } else {
+            None
+        };
+        Ok(Stml::TryCatch(token, Box::new(body), catches, finally))
+    }
+
+    fn for_in_stml(&mut self, label: Option<Rc<Token>>) -> Result<Stml, ()> {
+        let token = self.previous();
+        self.consume(&[TokenType::OParen])?;
+        let definable = self.definable()?;
+        let in_token = self.consume(&[TokenType::In])?;
+        let iterable = self.parse_expr()?;
+        self.consume(&[TokenType::CParen])?;
+        let body = self.block()?;
+        Ok(Stml::ForIn(
+            label,
+            token,
+            definable,
+            in_token,
+            iterable,
+            Box::new(body),
+        ))
+    }
+
+    /// Parses the patterns as expressions, leaving checking whether they are valid patterns to the compiler.
+    fn match_stml(&mut self) -> Result<Stml, ()> {
+        let token = self.previous();
+        let subject = self.parse_expr()?;
+        self.consume(&[TokenType::OBrace])?;
+        let mut arms = vec![];
+        let mut default = None;
+        // The default arm must be the last one.
+        while !self.check(&[TokenType::CBrace])? && !self.at_end()? && default.is_none() {
+            if self.check_consume(&[TokenType::Else])? {
+                let token = self.previous();
+                default = Some((token, Box::new(self.block()?)));
+                continue;
+            }
+            let pattern = self.parse_expr()?;
+            let guard = if self.check_consume(&[TokenType::If])? {
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+            arms.push((pattern, guard, self.block()?));
+        }
+        self.consume(&[TokenType::CBrace])?;
+        Ok(Stml::Match(token, subject, arms, default))
+    }
+
+    /// Parses the optional value of `أرجع`, `ألقي`, and `أنتج` which must be on the same line.
+    fn optional_value(&mut self) -> Result<Option<Expr>, ()> {
+        if self.check(&[TokenType::NewLine, TokenType::CBrace, TokenType::EOF])? {
+            Ok(None)
+        } else {
+            Ok(Some(self.parse_expr()?))
+        }
+    }
+
     fn expr_stml(&mut self) -> Result<Stml, ()> {
         Ok(Stml::Expr(self.parse_expr()?))
     }
@@ -338,31 +733,44 @@ impl Parser {
         if self.check_consume(&[TokenType::Import])? {
             self.import_stml()
         } else if self.check_consume(&[TokenType::Function])? {
-            todo!()
-        } else if self.check_consume(&[TokenType::Var])? {
-            todo!()
+            self.function_decl(None)
+        } else if self.check_consume(&[TokenType::Class])? {
+            self.class_decl(None)
+        } else if self.check_consume(&[TokenType::Var, TokenType::Const])? {
+            self.var_decl(None)
         } else if self.check_consume(&[TokenType::While])? {
-            todo!()
+            self.while_stml(None)
         } else if self.check_consume(&[TokenType::Loop])? {
-            todo!()
+            self.loop_stml(None)
         } else if self.check_consume(&[TokenType::If])? {
-            todo!()
+            self.if_stml()
         } else if self.check_consume(&[TokenType::Try])? {
-            todo!()
-        } else if self.check_consume(&[TokenType::OBrace])? {
-            todo!()
+            self.try_catch_stml()
+        } else if self.check(&[TokenType::OBrace])? {
+            self.block()
         } else if self.check_consume(&[TokenType::Break])? {
-            todo!()
+            let token = self.previous();
+            Ok(Stml::Break(token, self.optional_label()?))
         } else if self.check_consume(&[TokenType::Continue])? {
-            todo!()
+            let token = self.previous();
+            Ok(Stml::Continue(token, self.optional_label()?))
         } else if self.check_consume(&[TokenType::Return])? {
-            todo!()
+            let token = self.previous();
+            Ok(Stml::Return(token, self.optional_value()?))
         } else if self.check_consume(&[TokenType::Throw])? {
-            todo!()
+            let token = self.previous();
+            Ok(Stml::Throw(token, self.optional_value()?))
+        } else if self.check_consume(&[TokenType::Yield])? {
+            let token = self.previous();
+            Ok(Stml::Yield(token, self.optional_value()?))
         } else if self.check_consume(&[TokenType::Export])? {
-            todo!()
+            self.export_stml()
         } else if self.check_consume(&[TokenType::For])? {
-            todo!()
+            self.for_in_stml(None)
+        } else if self.check_label()? {
+            self.labeled_stml()
+        } else if self.check_consume(&[TokenType::Match])? {
+            self.match_stml()
         } else {
             self.expr_stml()
         }
@@ -374,7 +782,9 @@ impl Parser {
             && !self
                 .check(&[
                     TokenType::Function,
+                    TokenType::Class,
                     TokenType::Var,
+                    TokenType::Const,
                     TokenType::While,
                     TokenType::Loop,
                     TokenType::If,
@@ -384,9 +794,11 @@ impl Parser {
                     TokenType::Continue,
                     TokenType::Return,
                     TokenType::Throw,
+                    TokenType::Yield,
                     TokenType::Import,
                     TokenType::Export,
                     TokenType::For,
+                    TokenType::Match,
                 ])
                 .unwrap_or(false)
         {
@@ -396,8 +808,12 @@ impl Parser {
 
     #[allow(unused_must_use)]
     pub fn parse(mut self) -> Result<Vec<Stml>, Vec<Error>> {
-        println!("[PARSER] started");
-        self.validate_current();
+        if cfg!(feature = "verbose") {
+            println!("[PARSER] started")
+        }
+        if self.validate_current().is_err() {
+            self.sync()
+        }
         let mut ast = vec![];
         while !self.at_end().unwrap_or(false) {
             match self.stml() {
@@ -15,40 +15,40 @@ pub const OPERATORS: [(Option<u8>, Option<u8>, Option<u8>, Option<Associativity>
     (None, None, Some(1), None),                         // 4
     (None, None, None, None),                            // 5
     (None, None, Some(1), None),                         // 6
-    (None, None, None, None),                            // 7
-    (None, Some(4), None, Some(Associativity::Left)),    // 8
-    (Some(2), Some(4), None, Some(Associativity::Left)), // 9
-    (None, Some(3), None, Some(Associativity::Left)),    // 10
-    (None, Some(3), None, Some(Associativity::Left)),    // 11
-    (None, Some(3), None, Some(Associativity::Left)),    // 12
-    (None, None, None, None),                            // 13
+    (None, Some(6), None, Some(Associativity::Left)),    // 7
+    (None, None, None, None),                            // 8
+    (None, Some(5), None, Some(Associativity::Left)),    // 9
+    (Some(3), Some(5), None, Some(Associativity::Left)), // 10
+    (None, Some(4), None, Some(Associativity::Left)),    // 11
+    (None, Some(4), None, Some(Associativity::Left)),    // 12
+    (None, Some(4), None, Some(Associativity::Left)),    // 13
     (None, None, None, None),                            // 14
-    (None, None, None, None),                            // 15
-    (None, Some(9), None, Some(Associativity::Right)),   // 16
-    (None, Some(9), None, Some(Associativity::Right)),   // 17
-    (None, Some(9), None, Some(Associativity::Right)),   // 18
-    (None, Some(9), None, Some(Associativity::Right)),   // 19
-    (None, Some(9), None, Some(Associativity::Right)),   // 20
-    (None, Some(9), None, Some(Associativity::Right)),   // 21
-    (None, None, None, None),                            // 22
-    (None, None, None, None),                            // 23
-    (None, Some(6), None, Some(Associativity::Left)),    // 24
-    (Some(2), None, None, None),                         // 25
-    (None, Some(6), None, Some(Associativity::Left)),    // 26
-    (None, Some(5), None, Some(Associativity::Left)),    // 27
-    (None, Some(5), None, Some(Associativity::Left)),    // 28
-    (None, Some(5), None, Some(Associativity::Left)),    // 29
-    (None, Some(5), None, Some(Associativity::Left)),    // 30
-    (None, Some(7), None, Some(Associativity::Left)),    // 31
-    (None, Some(8), None, Some(Associativity::Left)),    // 32
-    (None, None, None, None),                            // 33
-    (None, None, None, None),                            // 34
-    (None, None, None, None),                            // 35
-    (None, None, None, None),                            // 36
-    (None, None, None, None),                            // 37
-    (None, None, None, None),                            // 38
-    (None, None, None, None),                            // 39
-    (None, None, None, None),                            // 40
+    (None, Some(15), None, Some(Associativity::Right)),  // 15
+    (None, None, Some(1), None),                         // 16
+    (None, Some(14), None, Some(Associativity::Left)),   // 17
+    (None, None, None, None),                            // 18
+    (None, Some(16), None, Some(Associativity::Right)),  // 19
+    (None, Some(16), None, Some(Associativity::Right)),  // 20
+    (None, Some(16), None, Some(Associativity::Right)),  // 21
+    (None, Some(16), None, Some(Associativity::Right)),  // 22
+    (None, Some(16), None, Some(Associativity::Right)),  // 23
+    (None, Some(16), None, Some(Associativity::Right)),  // 24
+    (None, Some(12), None, Some(Associativity::Left)),   // 25
+    (Some(3), None, None, None),                         // 26
+    (None, Some(12), None, Some(Associativity::Left)),   // 27
+    (None, Some(11), None, Some(Associativity::Left)),   // 28
+    (None, Some(11), None, Some(Associativity::Left)),   // 29
+    (None, Some(11), None, Some(Associativity::Left)),   // 30
+    (None, Some(11), None, Some(Associativity::Left)),   // 31
+    (None, Some(13), None, Some(Associativity::Left)),   // 32
+    (None, Some(14), None, Some(Associativity::Left)),   // 33
+    (None, Some(2), None, Some(Associativity::Right)),   // 34
+    (None, Some(4), None, Some(Associativity::Left)),    // 35
+    (None, Some(8), None, Some(Associativity::Left)),    // 36
+    (None, Some(9), None, Some(Associativity::Left)),    // 37
+    (Some(3), None, None, None),                         // 38
+    (None, Some(7), None, Some(Associativity::Left)),    // 39
+    (None, Some(7), None, Some(Associativity::Left)),    // 40
     (None, None, None, None),                            // 41
     (None, None, None, None),                            // 42
     (None, None, None, None),                            // 43
@@ -71,4 +71,23 @@ pub const OPERATORS: [(Option<u8>, Option<u8>, Option<u8>, Option<Associativity>
     (None, None, None, None),                            // 60
     (None, None, None, None),                            // 61
     (None, None, None, None),                            // 62
+    (None, None, None, None),                            // 63
+    (None, None, None, None),                            // 64
+    (None, None, None, None),                            // 65
+    (None, None, None, None),                            // 66
+    (None, None, None, None),                            // 67
+    (None, None, None, None),                            // 68
+    (None, Some(10), None, Some(Associativity::Left)),   // 69
+    (None, None, None, None),                            // 70
+    (None, Some(11), None, Some(Associativity::Left)),   // 71
+    (None, None, None, None),                            // 72
+    (None, None, None, None),                            // 73
+    (None, None, None, None),                            // 74
+    (None, None, None, None),                            // 75
+    (None, None, None, None),                            // 76
+    (None, None, None, None),                            // 77
+    (None, None, None, None),                            // 78
+    (None, None, None, None),                            // 79
+    (None, None, None, None),                            // 80
+    (None, None, None, None),                            // 81
 ];
@@ -1,24 +1,49 @@
+mod natives;
+
 use compiler::chunk::value::{
-    self, Arity, ArityType, Closure, DataType, Function, Iterable, Native, Object, Upvalue, Value,
+    self, Arity, ArityType, BoundMethod, Class, Closure, DataType, Function, Generator,
+    GeneratorState, Instance, Iterable, Module, Native, Object, OperationError, Upvalue, Value,
+    CONSTRUCTOR,
 };
-use compiler::chunk::{Chunk, Instruction, OpCode::*};
+use compiler::chunk::{gc, Chunk, Instruction, OpCode::*};
 use compiler::error::{Backtrace, RuntimeError};
 use lexer::token::Token;
-use std::collections::{HashMap, LinkedList};
-use std::ops::{Deref, DerefMut, Div, Mul, Rem, Sub};
+use std::collections::{HashMap, HashSet, LinkedList};
+use std::ops::{BitAnd, BitOr, BitXor, Deref, DerefMut, Div, Mul, Rem, Shl, Shr, Sub};
 use std::{cell::RefCell, cmp::Ordering, rc::Rc};
 
+/// The default maximum call depth.
+pub const MAX_DEPTH: usize = 1000;
+
 pub struct Vm {
+    /// The frames of the running calls, where the last one is the frame being executed.
+    frames: Vec<Frame>,
     tmps: Vec<Value>,
     locals: Vec<Value>,
     globals: HashMap<String, Value>,
+    /// The names of the globals defined with `ثابت`.
+    constants: HashSet<String>,
+    /// The maximum number of nested calls, exceeding it raises `RuntimeError::StackOverflow`.
+    max_depth: usize,
+    /// The modules that were imported, by their paths or by `@` followed by their names for the ones implemented by the VM.
+    modules: HashMap<String, Value>,
     open_upvalues: LinkedList<Rc<RefCell<Upvalue>>>,
 }
 
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Vm {
     pub fn new() -> Self {
+        Self::with_max_depth(MAX_DEPTH)
+    }
+
+    pub fn with_max_depth(max_depth: usize) -> Self {
         let qatam_print = Native::new(
-            |args: Vec<Value>| {
+            |args: Vec<Value>, _| {
                 println!("{}", args[1]);
                 Ok(Value::Nil)
             },
@@ -26,26 +51,24 @@ impl Vm {
         );
 
         Self {
+            frames: vec![],
             tmps: vec![],
             locals: vec![],
             globals: HashMap::from([("إطبع".to_owned(), Value::from(qatam_print))]),
+            constants: HashSet::new(),
+            max_depth,
+            modules: HashMap::new(),
             open_upvalues: LinkedList::new(),
         }
     }
 
     fn add_upvalue(&mut self, idx: usize) -> Rc<RefCell<Upvalue>> {
-        macro_rules! create_upvalue {
-            () => {
-                Rc::new(RefCell::new(Upvalue::Open(idx)))
-            };
-        }
-
         for (i, upvalue) in self.open_upvalues.clone().into_iter().enumerate() {
             let upvalue_idx = upvalue.borrow().clone().try_into().unwrap();
             match idx {
                 x if x < upvalue_idx => {
                     let after = self.open_upvalues.split_off(i);
-                    let new_upvalue = create_upvalue!();
+                    let new_upvalue = Upvalue::open(idx);
                     self.open_upvalues.push_back(Rc::clone(&new_upvalue));
                     for upvalue in after {
                         self.open_upvalues.push_back(upvalue)
@@ -58,25 +81,20 @@ impl Vm {
                 _ => {}
             }
         }
-        let new_upvalue = create_upvalue!();
+        let new_upvalue = Upvalue::open(idx);
         self.open_upvalues.push_back(Rc::clone(&new_upvalue));
         new_upvalue
     }
 
     /// Closes the upvalue with `idx` and the ones after it.
     fn close_upvalues(&mut self, idx: usize) {
-        loop {
-            match self.open_upvalues.back() {
-                Some(upvalue) => {
-                    let upvalue_idx: usize = upvalue.borrow().clone().try_into().unwrap();
-                    if upvalue_idx >= idx {
-                        let popped = self.open_upvalues.pop_back().unwrap();
-                        *popped.borrow_mut().deref_mut() =
-                            Upvalue::Closed(self.locals[upvalue_idx].clone());
-                    }
-                }
-                _ => break,
+        while let Some(upvalue) = self.open_upvalues.back() {
+            let upvalue_idx: usize = upvalue.borrow().clone().try_into().unwrap();
+            if upvalue_idx < idx {
+                break;
             }
+            let popped = self.open_upvalues.pop_back().unwrap();
+            *popped.borrow_mut().deref_mut() = Upvalue::Closed(self.locals[upvalue_idx].clone());
         }
     }
 
@@ -84,7 +102,18 @@ impl Vm {
         if cfg!(feature = "verbose") {
             println!("[VM] started")
         }
-        match Frame::new(self, Rc::new(chunk.into())).run() {
+        let slots = self.locals.len();
+        let tmps = self.tmps.len();
+        self.push_frame(Frame {
+            closure: Rc::new(chunk.into()),
+            ip: 0,
+            slots,
+            tmps,
+            argc: 0,
+            handlers: vec![],
+            ret: Return::Value,
+        });
+        match self.execute() {
             Ok(_) => {
                 if cfg!(feature = "verbose") {
                     println!("[VM] succeeded")
@@ -95,44 +124,40 @@ impl Vm {
                 if cfg!(feature = "verbose") {
                     println!("[VM] failed")
                 }
+                // The VM is reused (e.g. by the REPL), so what the failed frames left is dropped.
+                self.close_upvalues(slots);
+                self.locals.truncate(slots);
+                self.tmps.truncate(tmps);
                 Err(err)
             }
         }
     }
-}
 
-struct Frame<'a> {
-    state: &'a mut Vm,
-    closure: Rc<Closure>,
-    ip: usize,
-    slots: usize,
-    idx: usize,
-    handlers: Vec<Handler>,
-}
+    fn frame(&self) -> &Frame {
+        self.frames.last().unwrap()
+    }
 
-impl<'a> Frame<'a> {
-    fn new(state: &'a mut Vm, closure: Rc<Closure>) -> Self {
-        Self {
-            state,
-            closure,
-            ip: 0,
-            slots: 0,
-            idx: 0,
-            handlers: vec![],
-        }
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
     }
 
-    fn new_function(state: &'a mut Vm, closure: Rc<Closure>, argc: usize, idx: usize) -> Self {
-        Self {
-            ip: closure.start_ip(argc),
-            slots: state.locals.len(),
-            state,
-            closure,
-            idx,
-            handlers: vec![],
+    fn push_frame(&mut self, frame: Frame) {
+        self.frames.push(frame);
+        if cfg!(feature = "verbose") {
+            println!(
+                "[VM] {}'s chunk",
+                Value::Object(Object::Closure(Rc::clone(&self.frame().closure)))
+            )
         }
     }
 
+    /// Moves the current frame past the instruction that called the frame that was just popped.
+    fn advance(&mut self) {
+        let frame = self.frame_mut();
+        let size = frame.closure.chunk().read(frame.ip).unwrap().size();
+        frame.ip += size;
+    }
+
     fn check_type(&self, value: &Value, expected: &[DataType]) -> Result<(), RuntimeError> {
         let received = value.typ();
         if expected.contains(&received) {
@@ -140,13 +165,25 @@ impl<'a> Frame<'a> {
         } else {
             Err(RuntimeError::Type(
                 expected.to_owned(),
-                received,
+                vec![received],
                 self.token(),
                 Backtrace::default(),
             ))
         }
     }
 
+    /// Turns the failure of an operator on values into an error at the current instruction.
+    fn operands_err(&self, err: OperationError) -> RuntimeError {
+        match err {
+            OperationError::Type(expected, received) => {
+                RuntimeError::Type(expected, received, self.token(), Backtrace::default())
+            }
+            OperationError::NotInteger(number) => {
+                RuntimeError::NotInteger(number, self.token(), Backtrace::default())
+            }
+        }
+    }
+
     fn check_arity(&self, arity: &Arity, argc: usize) -> Result<(), RuntimeError> {
         match argc {
             x if x >= arity.required() && x <= arity.required() + arity.optional() => Ok(()),
@@ -162,32 +199,217 @@ impl<'a> Frame<'a> {
         }
     }
 
+    /// Fails when a frame called from the current one would be deeper than `max_depth`.
+    fn check_depth(&self) -> Result<(), RuntimeError> {
+        if self.frames.len() > self.max_depth {
+            Err(RuntimeError::StackOverflow(
+                self.max_depth,
+                self.token(),
+                Backtrace::default(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Calls the value that is below the top `argc` values, which are its arguments.
+    fn call(&mut self, argc: usize) -> Result<(), RuntimeError> {
+        let tmps_len = self.tmps.len();
+        let idx = tmps_len - argc - 1;
+        match self.tmps[idx].clone() {
+            Value::Object(Object::Closure(closure)) => {
+                self.call_closure(closure, argc, idx, Return::Value)?
+            }
+            Value::Object(Object::BoundMethod(bound_method)) => {
+                self.tmps[idx] = bound_method.receiver();
+                self.call_closure(bound_method.method(), argc, idx, Return::Value)?
+            }
+            Value::Object(Object::Class(class)) => {
+                let instance = Value::from(Instance::new(Rc::clone(&class)));
+                match class.method(CONSTRUCTOR) {
+                    Some(constructor) => {
+                        self.tmps[idx] = instance.clone();
+                        self.call_closure(constructor, argc, idx, Return::Instance(instance))?;
+                    }
+                    None => {
+                        self.check_arity(&Arity::default(), argc)?;
+                        self.tmps.truncate(idx);
+                        self.push(instance)
+                    }
+                }
+            }
+            Value::Object(Object::Native(native)) => {
+                self.check_arity(native.arity(), argc)?;
+                let args = self.tmps.drain(idx..).collect::<Vec<_>>();
+                self.push(native.call(args, self.token())?)
+            }
+            callee => {
+                return Err(RuntimeError::Uncallable(
+                    callee.typ(),
+                    self.token(),
+                    Backtrace::default(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes the frame of `closure` which is at `idx` in tmps followed by its arguments.
+    fn call_closure(
+        &mut self,
+        closure: Rc<Closure>,
+        argc: usize,
+        idx: usize,
+        ret: Return,
+    ) -> Result<(), RuntimeError> {
+        self.check_arity(closure.arity(), argc)?;
+        if closure.is_generator() {
+            let tmps = self.tmps.drain(idx..).collect::<Vec<_>>();
+            let generator = Generator::new(closure, argc, tmps);
+            self.push(match ret {
+                Return::Instance(instance) => instance,
+                _ => Value::from(Iterable::from(generator)),
+            });
+            return Ok(());
+        }
+        self.check_depth()?;
+        self.push_frame(Frame {
+            ip: closure.start_ip(argc),
+            slots: self.locals.len(),
+            tmps: idx,
+            argc,
+            handlers: vec![],
+            ret,
+            closure,
+        });
+        Ok(())
+    }
+
+    /// Pushes the frame of `generator` to run it until it yields its next value, returns `false` without pushing anything when it is finished.
+    fn resume(&mut self, generator: Rc<RefCell<Generator>>) -> Result<bool, RuntimeError> {
+        self.check_depth()?;
+        let state = match generator.borrow_mut().take_state() {
+            Some(state) => state,
+            None => return Ok(false),
+        };
+        let (closure, argc) = {
+            let generator = generator.borrow();
+            (generator.closure(), generator.argc())
+        };
+        let slots = self.locals.len();
+        let tmps_len = self.tmps.len();
+        self.locals.extend(state.locals);
+        self.tmps.extend(state.tmps);
+        // The values were changed through the closed upvalues while the generator was suspended.
+        for (idx, upvalue) in state.upvalues {
+            if let Upvalue::Closed(value) = upvalue.replace(Upvalue::Open(slots + idx)) {
+                self.locals[slots + idx] = value
+            }
+            self.open_upvalues.push_back(upvalue)
+        }
+        self.push_frame(Frame {
+            closure,
+            ip: state.ip,
+            slots,
+            tmps: tmps_len,
+            argc,
+            handlers: state
+                .handlers
+                .into_iter()
+                .map(|(ip, handler_slots, handler_tmps)| {
+                    Handler::new(ip, slots + handler_slots, tmps_len + handler_tmps)
+                })
+                .collect(),
+            ret: Return::Generator(generator),
+        });
+        Ok(true)
+    }
+
+    /// Pops the current frame giving `value` to its caller.
+    fn ret(&mut self, value: Value) {
+        let frame = self.frames.pop().unwrap();
+        self.close_upvalues(frame.slots);
+        self.locals.drain(frame.slots..);
+        // Returning from inside a loop or a match leaves its temporaries behind.
+        self.tmps.truncate(frame.tmps);
+        match frame.ret {
+            Return::Value => self.push(value),
+            Return::Instance(instance) => self.push(instance),
+            Return::Module(path) => {
+                self.modules.insert(path, value.clone());
+                self.push(value)
+            }
+            // The `FOR_ITER` that resumed the generator runs again, finding it finished.
+            Return::Generator(_) => return,
+        }
+        self.advance()
+    }
+
+    /// Pops the current frame, which must be a generator's, saving its state to be resumed from `ip` and giving `value` to the `FOR_ITER` that resumed it.
+    fn suspend(&mut self, value: Value, ip: usize) {
+        let frame = self.frames.pop().unwrap();
+        let generator = match frame.ret {
+            Return::Generator(generator) => generator,
+            _ => unreachable!(),
+        };
+        let handlers = frame
+            .handlers
+            .iter()
+            .map(|handler| {
+                (
+                    handler.ip(),
+                    handler.slots() - frame.slots,
+                    handler.tmps() - frame.tmps,
+                )
+            })
+            .collect();
+        // Closed so that the closures which captured the generator's locals can still use them while it's suspended.
+        let upvalues = self
+            .open_upvalues
+            .iter()
+            .filter_map(|upvalue| {
+                let idx: usize = upvalue.borrow().clone().try_into().unwrap();
+                (idx >= frame.slots).then(|| (idx - frame.slots, Rc::clone(upvalue)))
+            })
+            .collect();
+        self.close_upvalues(frame.slots);
+        generator.borrow_mut().suspend(GeneratorState {
+            ip,
+            locals: self.locals.drain(frame.slots..).collect(),
+            tmps: self.tmps.drain(frame.tmps..).collect(),
+            handlers,
+            upvalues,
+        });
+        self.push(value);
+        self.advance()
+    }
+
     fn chunk(&self) -> &Chunk {
-        self.closure.chunk()
+        self.frame().closure.chunk()
     }
 
     fn token(&self) -> Rc<Token> {
-        self.chunk().token(self.ip)
+        self.chunk().token(self.frame().ip)
     }
 
     fn local(&self, idx: usize) -> &Value {
-        &self.state.locals[idx]
+        &self.locals[idx]
     }
 
     fn local_mut(&mut self, idx: usize) -> &mut Value {
-        &mut self.state.locals[idx]
+        &mut self.locals[idx]
     }
 
     fn push_local(&mut self, value: Value) {
-        self.state.locals.push(value)
+        self.locals.push(value)
     }
 
     fn pop_local(&mut self) -> Value {
-        self.state.locals.pop().unwrap()
+        self.locals.pop().unwrap()
     }
 
     fn pop(&mut self) -> Value {
-        self.state.tmps.pop().unwrap()
+        self.tmps.pop().unwrap()
     }
 
     fn pop_typed(&mut self, expected: &[DataType]) -> Result<Value, RuntimeError> {
@@ -197,7 +419,7 @@ impl<'a> Frame<'a> {
     }
 
     fn last(&self) -> &Value {
-        self.state.tmps.last().unwrap()
+        self.tmps.last().unwrap()
     }
 
     fn last_typed(&self, expected: &[DataType]) -> Result<&Value, RuntimeError> {
@@ -207,17 +429,18 @@ impl<'a> Frame<'a> {
     }
 
     fn push(&mut self, value: Value) {
-        self.state.tmps.push(value)
+        self.tmps.push(value)
     }
 
-    fn run_instr(&mut self, instr: Instruction) -> Result<(Option<Value>, bool), RuntimeError> {
-        let mut returned = None;
+    /// Returns whether the ip should move to the next instruction, which is `false` after jumps.
+    fn run_instr(&mut self, instr: Instruction) -> Result<bool, RuntimeError> {
         let mut advance = true;
         macro_rules! numeric_arith_op {
             ($method:ident) => {{
-                let b = self.pop_typed(&[DataType::Number])?;
-                let a = self.pop_typed(&[DataType::Number])?;
-                self.push(Value::$method(a, b))
+                let b = self.pop();
+                let a = self.pop();
+                let res = Value::$method(a, b).map_err(|err| self.operands_err(err))?;
+                self.push(res)
             }};
         }
         macro_rules! eq_op {
@@ -227,19 +450,20 @@ impl<'a> Frame<'a> {
                 self.push(Value::from(Value::$method(&a, &b)))
             }};
         }
-        macro_rules! numeric_cmp_op {
+        macro_rules! cmp_op {
             ($($ordering:expr),+) => {{
-                let b = self.pop_typed(&[DataType::Number])?;
-                let a = self.pop_typed(&[DataType::Number])?;
-                let res = Value::partial_cmp(&a, &b).unwrap();
-                self.push(Value::from($(res == $ordering)||+))
+                let b = self.pop();
+                let a = self.pop();
+                // Comparisons involving NaN are always false.
+                let res = a.compare(&b).map_err(|err| self.operands_err(err))?;
+                self.push(Value::from($(res == Some($ordering))||+))
             }};
         }
         macro_rules! jump_if_x_or_pop {
             ($x:expr) => {{
                 let offset = instr.read_two_bytes_oper(0);
                 if $x {
-                    self.ip += offset;
+                    self.frame_mut().ip += offset;
                     advance = false;
                 } else {
                     self.pop();
@@ -250,15 +474,16 @@ impl<'a> Frame<'a> {
             ($x:expr) => {{
                 let offset = instr.read_two_bytes_oper(0);
                 if $x {
-                    self.ip += offset;
+                    self.frame_mut().ip += offset;
                     advance = false;
                 }
             }};
         }
         match instr.op_code() {
             NEG => {
-                let value = self.pop_typed(&[DataType::Number])?;
-                self.push(-value)
+                let value = self.pop();
+                let res = (-value).map_err(|err| self.operands_err(err))?;
+                self.push(res)
             }
             NOT => {
                 let value = self.pop();
@@ -266,31 +491,74 @@ impl<'a> Frame<'a> {
             }
             ADD => {
                 let b = self.pop();
-                let a = self.pop_typed(&[DataType::Number, DataType::String, DataType::List])?;
-                self.check_type(&b, &[a.typ()])?;
-                self.push(a + b)
+                let a = self.pop();
+                let res = (a + b).map_err(|err| self.operands_err(err))?;
+                self.push(res)
             }
             SUB => numeric_arith_op!(sub),
             MUL => numeric_arith_op!(mul),
             DIV => numeric_arith_op!(div),
             REM => numeric_arith_op!(rem),
+            POW => numeric_arith_op!(pow),
+            FLOOR_DIV => numeric_arith_op!(floor_div),
+            BIT_AND => numeric_arith_op!(bitand),
+            BIT_OR => numeric_arith_op!(bitor),
+            BIT_XOR => numeric_arith_op!(bitxor),
+            SHL => numeric_arith_op!(shl),
+            SHR => numeric_arith_op!(shr),
+            BIT_NOT => {
+                let value = self.pop();
+                let res = value.bit_not().map_err(|err| self.operands_err(err))?;
+                self.push(res)
+            }
             EQ => eq_op!(eq),
             NOT_EQ => eq_op!(ne),
-            GREATER => numeric_cmp_op!(Ordering::Greater),
-            GREATER_EQ => numeric_cmp_op!(Ordering::Greater, Ordering::Equal),
-            LESS => numeric_cmp_op!(Ordering::Less),
-            LESS_EQ => numeric_cmp_op!(Ordering::Less, Ordering::Equal),
+            GREATER => cmp_op!(Ordering::Greater),
+            GREATER_EQ => cmp_op!(Ordering::Greater, Ordering::Equal),
+            LESS => cmp_op!(Ordering::Less),
+            LESS_EQ => cmp_op!(Ordering::Less, Ordering::Equal),
+            CONTAINS => {
+                let container = self.pop_typed(&[
+                    DataType::String,
+                    DataType::List,
+                    DataType::HashMap,
+                    DataType::Module,
+                ])?;
+                let value = self.pop();
+                let contains = match container {
+                    Value::Object(Object::List(list)) => list.borrow().contains(&value),
+                    Value::Object(Object::Module(module)) => {
+                        self.check_type(&value, &[DataType::String])?;
+                        let key: String = value.try_into().unwrap();
+                        module.exports().borrow().contains_key(&key)
+                    }
+                    Value::Object(Object::HashMap(hash_map)) => {
+                        self.check_type(&value, &[DataType::String])?;
+                        let key: String = value.try_into().unwrap();
+                        hash_map.borrow().contains_key(&key)
+                    }
+                    Value::String(string) => {
+                        self.check_type(&value, &[DataType::String])?;
+                        let substring: String = value.try_into().unwrap();
+                        string.contains(&substring)
+                    }
+                    _ => unreachable!(),
+                };
+                self.push(Value::from(contains))
+            }
             CONST8 | CONST16 => {
                 let idx = instr.read_oper(instr.size() - 1, 0);
                 self.push(self.chunk().constant(idx))
             }
             JUMP => {
                 let offset = instr.read_two_bytes_oper(0);
-                self.ip += offset;
+                self.frame_mut().ip += offset;
                 advance = false;
             }
             JUMP_IF_FALSY_OR_POP => jump_if_x_or_pop!(!self.last().truthy()),
             JUMP_IF_TRUTHY_OR_POP => jump_if_x_or_pop!(self.last().truthy()),
+            JUMP_IF_NOT_NIL_OR_POP => jump_if_x_or_pop!(*self.last() != Value::Nil),
+            JUMP_IF_NIL => jump_if_x!(*self.last() == Value::Nil),
             POP_JUMP_IF_FALSY => jump_if_x!(!self.pop().truthy()),
             POP_JUMP_IF_TRUTHY => jump_if_x!(self.pop().truthy()),
             FOR_ITER => {
@@ -300,27 +568,38 @@ impl<'a> Frame<'a> {
                     .clone()
                     .try_into()
                     .unwrap();
-                let mut iterator = iterator.borrow_mut();
-                match iterator.next() {
+                let generator = iterator.borrow().generator();
+                let next = match generator {
+                    Some(generator) => {
+                        // The generator's frame gives the next value when it yields.
+                        if self.resume(generator)? {
+                            return Ok(true);
+                        }
+                        None
+                    }
+                    None => iterator.borrow_mut().next(),
+                };
+                match next {
                     Some(value) => self.push(value),
                     None => {
-                        self.ip += offset;
+                        self.pop();
+                        self.frame_mut().ip += offset;
                         advance = false;
                     }
                 }
             }
             LOOP => {
                 let offset = instr.read_two_bytes_oper(0);
-                self.ip -= offset;
+                self.frame_mut().ip -= offset;
                 advance = false;
             }
             GET_LOCAL => {
                 let idx = instr.read_byte_oper(0);
-                self.push(self.local(self.slots + idx).clone())
+                self.push(self.local(self.frame().slots + idx).clone())
             }
             SET_LOCAL => {
                 let idx = instr.read_byte_oper(0);
-                *self.local_mut(self.slots + idx) = self.last().clone();
+                *self.local_mut(self.frame().slots + idx) = self.last().clone();
             }
             DEF_LOCAL => {
                 let value = self.pop();
@@ -331,35 +610,29 @@ impl<'a> Frame<'a> {
             }
             GET_UPVALUE => {
                 let idx = instr.read_byte_oper(0);
-                self.push(match self.closure.upvalue(idx).borrow().deref() {
+                self.push(match self.frame().closure.upvalue(idx).borrow().deref() {
                     Upvalue::Closed(value) => value.clone(),
                     Upvalue::Open(idx) => self.local(*idx).clone(),
                 })
             }
             SET_UPVALUE => {
                 let idx = instr.read_byte_oper(0);
-                if let Upvalue::Closed(value) = self.closure.upvalue(idx).borrow_mut().deref_mut() {
-                    *value = self.last().clone()
-                } else {
-                    let idx: usize = self
-                        .closure
-                        .upvalue(idx)
-                        .borrow()
-                        .clone()
-                        .try_into()
-                        .unwrap();
-                    *self.local_mut(idx) = self.last().clone();
+                let upvalue = self.frame().closure.upvalue(idx);
+                let mut upvalue = upvalue.borrow_mut();
+                match upvalue.deref_mut() {
+                    Upvalue::Closed(value) => *value = self.last().clone(),
+                    Upvalue::Open(idx) => *self.local_mut(*idx) = self.last().clone(),
                 }
             }
             CLOSE_UPVALUE => {
-                let idx = self.state.locals.len() - 1;
-                self.state.close_upvalues(idx);
+                let idx = self.locals.len() - 1;
+                self.close_upvalues(idx);
                 self.pop_local();
             }
             GET_GLOBAL8 | GET_GLOBAL16 => {
                 let idx = instr.read_oper(instr.size() - 1, 0);
                 let name: String = self.chunk().constant(idx).try_into().unwrap();
-                let value = match self.state.globals.get(&name) {
+                let value = match self.globals.get(&name) {
                     Some(value) => value.clone(),
                     None => {
                         return Err(RuntimeError::Name(name, self.token(), Backtrace::default()))
@@ -371,19 +644,29 @@ impl<'a> Frame<'a> {
                 let idx = instr.read_oper(instr.size() - 1, 0);
                 let name: String = self.chunk().constant(idx).try_into().unwrap();
                 let new_value = self.last().clone();
-                match self.state.globals.get_mut(&name) {
+                if self.constants.contains(&name) {
+                    return Err(RuntimeError::Constant(
+                        name,
+                        self.token(),
+                        Backtrace::default(),
+                    ));
+                }
+                match self.globals.get_mut(&name) {
                     Some(value) => *value = new_value,
                     None => {
                         return Err(RuntimeError::Name(name, self.token(), Backtrace::default()))
                     }
                 }
             }
-            DEF_GLOBAL8 | DEF_GLOBAL16 => {
+            DEF_GLOBAL8 | DEF_GLOBAL16 | DEF_CONST_GLOBAL8 | DEF_CONST_GLOBAL16 => {
                 let idx = instr.read_oper(instr.size() - 1, 0);
                 let name: String = self.chunk().constant(idx).try_into().unwrap();
                 let value = self.pop();
-                if !self.state.globals.contains_key(&name) || name == "_" {
-                    self.state.globals.insert(name, value);
+                if !self.globals.contains_key(&name) || name == "_" {
+                    if matches!(instr.op_code(), DEF_CONST_GLOBAL8 | DEF_CONST_GLOBAL16) {
+                        self.constants.insert(name.clone());
+                    }
+                    self.globals.insert(name, value);
                 } else {
                     return Err(RuntimeError::AlreadyDefined(
                         name,
@@ -407,63 +690,176 @@ impl<'a> Frame<'a> {
                     let local = instr.read_byte_oper(offset) != 0;
                     let idx = instr.read_byte_oper(offset + 1);
                     if local {
-                        upvalues.push(self.state.add_upvalue(idx))
+                        upvalues.push(self.add_upvalue(self.frame().slots + idx))
                     } else {
-                        upvalues.push(self.closure.upvalue(idx))
+                        upvalues.push(self.frame().closure.upvalue(idx))
                     }
                 }
                 self.push(Value::from(Closure::new(function, upvalues)))
             }
             CALL => {
-                // TODO add stack overflowing
                 let argc = instr.read_byte_oper(0);
-                let tmps_len = self.state.tmps.len();
-                let idx = tmps_len - argc - 1;
-                match self.state.tmps[idx].clone() {
-                    Value::Object(Object::Closure(closure)) => {
-                        self.check_arity(closure.arity(), argc)?;
-                        let value = Frame::new_function(self.state, closure, argc, self.idx + 1)
-                            .run()?
-                            .unwrap();
-                        self.push(value);
-                        if cfg!(feature = "verbose") {
-                            println!(
-                                "[VM] {}'s chunk",
-                                Value::Object(Object::Closure(Rc::clone(&self.closure)))
-                            )
-                        }
+                self.call(argc)?
+            }
+            CALL_LIST => {
+                let list: Rc<RefCell<Vec<Value>>> =
+                    self.pop_typed(&[DataType::List])?.try_into().unwrap();
+                let args = list.borrow().clone();
+                let argc = args.len();
+                self.tmps.extend(args);
+                self.call(argc)?
+            }
+            IMPORT8 | IMPORT16 => {
+                let idx = instr.read_oper(instr.size() - 1, 0);
+                let path: String = self.chunk().constant(idx).try_into().unwrap();
+                match self.modules.get(&path).cloned() {
+                    Some(module) => {
+                        self.pop();
+                        self.push(module)
                     }
-                    Value::Object(Object::Native(native)) => {
-                        self.check_arity(native.arity(), argc)?;
-                        let args = self.state.tmps.drain(idx..).collect::<Vec<_>>();
-                        self.push(native.call(args)?)
+                    None => {
+                        let closure: Rc<Closure> = self.last().clone().try_into().unwrap();
+                        let idx = self.tmps.len() - 1;
+                        self.call_closure(closure, 0, idx, Return::Module(path))?
                     }
-                    _ => todo!("Add Uncallable error type"),
                 }
             }
+            NATIVE_MODULE8 | NATIVE_MODULE16 => {
+                let idx = instr.read_oper(instr.size() - 1, 0);
+                let name: String = self.chunk().constant(idx).try_into().unwrap();
+                let module = self
+                    .modules
+                    .entry(format!("@{name}"))
+                    .or_insert_with(|| Value::from(natives::module(&name)))
+                    .clone();
+                self.push(module)
+            }
             BUILD_VARIADIC => {
-                let arity = self.closure.arity();
+                let arity = self.frame().closure.arity();
+                // The arguments that didn't fit in the required and the optional parameters are on the top.
                 let additional = self
-                    .state
-                    .tmps
-                    .drain(arity.required() + arity.optional() + 1..) // https://share.sketchpad.app/22/36b-20f7-cd4981.png
-                    .collect::<Vec<_>>();
+                    .frame()
+                    .argc
+                    .saturating_sub(arity.required() + arity.optional());
+                let tmps_len = self.tmps.len();
+                let additional = self.tmps.drain(tmps_len - additional..).collect::<Vec<_>>();
                 self.push(Value::from(additional))
             }
             RET => {
-                self.state.close_upvalues(self.slots);
-                self.state.locals.drain(self.slots..);
-                returned = Some(self.pop())
+                let value = self.pop();
+                self.ret(value)
+            }
+            YIELD => {
+                let value = self.pop();
+                let ip = self.frame().ip + instr.size();
+                self.suspend(value, ip)
             }
             BUILD_LIST => {
                 let size = instr.read_two_bytes_oper(0);
                 let list = self
-                    .state
                     .tmps
-                    .drain(self.state.tmps.len() - size..)
+                    .drain(self.tmps.len() - size..)
                     .collect::<Vec<_>>();
                 self.push(Value::from(list))
             }
+            CLASS => {
+                let name: String = self.pop().try_into().unwrap();
+                self.push(Value::from(Class::new(name)))
+            }
+            INHERIT => {
+                let parent: Rc<Class> = self.pop_typed(&[DataType::Class])?.try_into().unwrap();
+                let class: Rc<Class> = self.last().clone().try_into().unwrap();
+                class.inherit(&parent)
+            }
+            METHOD => {
+                let method: Rc<Closure> = self.pop().try_into().unwrap();
+                let class: Rc<Class> = self.last().clone().try_into().unwrap();
+                class.add_method(method)
+            }
+            GET_SUPER => {
+                let name: String = self.pop().try_into().unwrap();
+                let parent: Rc<Class> = self.pop().try_into().unwrap();
+                let receiver = self.pop();
+                match parent.method(&name) {
+                    Some(method) => self.push(Value::from(BoundMethod::new(receiver, method))),
+                    None => {
+                        return Err(RuntimeError::UndefinedKey(
+                            name,
+                            self.token(),
+                            Backtrace::default(),
+                        ))
+                    }
+                }
+            }
+            MATCH_LIST | MATCH_LIST_REST => {
+                let size = instr.read_two_bytes_oper(0);
+                let matched = match self.pop() {
+                    Value::Object(Object::List(list)) if instr.op_code() == MATCH_LIST => {
+                        list.borrow().len() == size
+                    }
+                    Value::Object(Object::List(list)) => list.borrow().len() >= size,
+                    _ => false,
+                };
+                self.push(Value::from(matched))
+            }
+            MATCH_HASH_MAP => {
+                let matched = matches!(self.pop(), Value::Object(Object::HashMap(..)));
+                self.push(Value::from(matched))
+            }
+            MATCH_KEY => {
+                let key: String = self.pop().try_into().unwrap();
+                let hash_map: Rc<RefCell<HashMap<String, Value>>> = self.pop().try_into().unwrap();
+                let matched = hash_map.borrow().contains_key(&key);
+                self.push(Value::from(matched))
+            }
+            LIST_REST => {
+                let from = instr.read_two_bytes_oper(0);
+                let list: Rc<RefCell<Vec<Value>>> =
+                    self.pop_typed(&[DataType::List])?.try_into().unwrap();
+                let rest = list.borrow()[from..].to_vec();
+                self.push(Value::from(rest))
+            }
+            EXTEND_LIST => {
+                let extension: Rc<RefCell<Vec<Value>>> =
+                    self.pop_typed(&[DataType::List])?.try_into().unwrap();
+                let list: Rc<RefCell<Vec<Value>>> = self.last().clone().try_into().unwrap();
+                let extension = extension.borrow().clone();
+                list.borrow_mut().extend(extension);
+            }
+            MERGE_HASH_MAP => {
+                let other: Rc<RefCell<HashMap<String, Value>>> =
+                    match self.pop_typed(&[DataType::HashMap, DataType::Module])? {
+                        Value::Object(Object::Module(module)) => module.exports(),
+                        popped => popped.try_into().unwrap(),
+                    };
+                let hash_map: Rc<RefCell<HashMap<String, Value>>> =
+                    self.last().clone().try_into().unwrap();
+                let other = other.borrow().clone();
+                hash_map.borrow_mut().extend(other);
+            }
+            BUILD_RANGE => {
+                let step = self.pop_typed(&[DataType::Number])?;
+                let end = self.pop_typed(&[DataType::Number])?;
+                let start = self.pop_typed(&[DataType::Number])?;
+                match (start, end, step) {
+                    (_, _, Value::Number(0.0)) => {
+                        return Err(RuntimeError::ZeroStep(self.token(), Backtrace::default()))
+                    }
+                    (Value::Number(start), Value::Number(end), Value::Number(step)) => {
+                        self.push(Value::from(Iterable::Range(start, end, step)))
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            BUILD_STRING => {
+                let size = instr.read_two_bytes_oper(0);
+                let string = self
+                    .tmps
+                    .drain(self.tmps.len() - size..)
+                    .map(|value| value.to_string())
+                    .collect::<String>();
+                self.push(Value::from(string))
+            }
             BUILD_HASH_MAP => {
                 let size = instr.read_two_bytes_oper(0);
                 let mut hash_map = HashMap::new();
@@ -474,10 +870,32 @@ impl<'a> Frame<'a> {
                 }
                 self.push(Value::from(hash_map))
             }
-            GET => {
+            BUILD_MODULE => {
+                let size = instr.read_two_bytes_oper(0);
+                let constants = self
+                    .tmps
+                    .drain(self.tmps.len() - size..)
+                    .map(|value| value.try_into().unwrap())
+                    .collect();
+                let exports = self.pop().try_into().unwrap();
+                self.push(Value::from(Module::new(exports, constants)))
+            }
+            GET | GET_OPTIONAL => {
+                let optional = instr.op_code() == GET_OPTIONAL;
                 let key = self.pop();
-                let popped =
-                    self.pop_typed(&[DataType::String, DataType::List, DataType::HashMap])?;
+                let popped = match self.pop_typed(&[
+                    DataType::String,
+                    DataType::List,
+                    DataType::HashMap,
+                    DataType::Instance,
+                    DataType::Error,
+                    DataType::Module,
+                ])? {
+                    Value::Object(Object::Module(module)) => {
+                        Value::Object(Object::HashMap(module.exports()))
+                    }
+                    popped => popped,
+                };
                 let value = match &popped {
                     Value::String(..) | Value::Object(Object::List(..)) => {
                         let idx: usize = key.try_into().map_err(|_| {
@@ -486,6 +904,7 @@ impl<'a> Frame<'a> {
                         match popped {
                             Value::String(string) => match string.chars().nth(idx) {
                                 Some(c) => Value::from(c),
+                                None if optional => Value::Nil,
                                 None => {
                                     return Err(RuntimeError::OutOfRange(
                                         idx,
@@ -497,6 +916,7 @@ impl<'a> Frame<'a> {
                             },
                             Value::Object(Object::List(list)) => match list.borrow().get(idx) {
                                 Some(value) => value.clone(),
+                                None if optional => Value::Nil,
                                 None => {
                                     return Err(RuntimeError::OutOfRange(
                                         idx,
@@ -514,6 +934,44 @@ impl<'a> Frame<'a> {
                         let key: String = key.try_into().unwrap();
                         match hash_map.borrow().get(&key).cloned() {
                             Some(value) => value,
+                            None if optional => Value::Nil,
+                            None => {
+                                return Err(RuntimeError::UndefinedKey(
+                                    key,
+                                    self.token(),
+                                    Backtrace::default(),
+                                ))
+                            }
+                        }
+                    }
+                    Value::Object(Object::Instance(instance)) => {
+                        self.check_type(&key, &[DataType::String])?;
+                        let key: String = key.try_into().unwrap();
+                        let instance_ref = instance.borrow();
+                        // Fields shadow methods.
+                        match instance_ref.field(&key) {
+                            Some(value) => value,
+                            None => match instance_ref.class().method(&key) {
+                                Some(method) => {
+                                    Value::from(BoundMethod::new(popped.clone(), method))
+                                }
+                                None if optional => Value::Nil,
+                                None => {
+                                    return Err(RuntimeError::UndefinedKey(
+                                        key,
+                                        self.token(),
+                                        Backtrace::default(),
+                                    ))
+                                }
+                            },
+                        }
+                    }
+                    Value::Object(Object::Error(err)) => {
+                        self.check_type(&key, &[DataType::String])?;
+                        let key: String = key.try_into().unwrap();
+                        match err.property(&key) {
+                            Some(value) => value,
+                            None if optional => Value::Nil,
                             None => {
                                 return Err(RuntimeError::UndefinedKey(
                                     key,
@@ -529,7 +987,12 @@ impl<'a> Frame<'a> {
             }
             SET => {
                 let key = self.pop();
-                let popped = self.pop_typed(&[DataType::List, DataType::HashMap])?;
+                let popped = self.pop_typed(&[
+                    DataType::List,
+                    DataType::HashMap,
+                    DataType::Instance,
+                    DataType::Module,
+                ])?;
                 let new_value = self.last().clone();
                 match popped {
                     Value::Object(Object::List(list)) => {
@@ -555,29 +1018,57 @@ impl<'a> Frame<'a> {
                         let key: String = key.try_into().unwrap();
                         hash_map.borrow_mut().insert(key, new_value);
                     }
+                    Value::Object(Object::Instance(instance)) => {
+                        self.check_type(&key, &[DataType::String])?;
+                        let key: String = key.try_into().unwrap();
+                        instance.borrow_mut().set_field(key, new_value);
+                    }
+                    Value::Object(Object::Module(module)) => {
+                        self.check_type(&key, &[DataType::String])?;
+                        let key: String = key.try_into().unwrap();
+                        if module.is_constant(&key) {
+                            return Err(RuntimeError::Constant(
+                                key,
+                                self.token(),
+                                Backtrace::default(),
+                            ));
+                        }
+                        module.exports().borrow_mut().insert(key, new_value);
+                    }
                     _ => unreachable!(),
                 }
             }
             APPEND_HANDLER => {
                 let offset = instr.read_two_bytes_oper(0);
-                self.handlers
-                    .push(Handler::new(self.ip + offset, self.state.locals.len()))
+                let handler =
+                    Handler::new(self.frame().ip + offset, self.locals.len(), self.tmps.len());
+                self.frame_mut().handlers.push(handler)
             }
             POP_HANDLER => {
-                self.handlers.pop();
-            }
-            THROW => {
-                let value = self.pop();
-                return Err(RuntimeError::User(
-                    value,
-                    self.token(),
-                    Backtrace::default(),
-                ));
+                self.frame_mut().handlers.pop();
             }
+            THROW => match self.pop() {
+                // Caught errors are thrown again as they are.
+                Value::Object(Object::Error(err)) => return Err(err.as_ref().clone()),
+                value => {
+                    return Err(RuntimeError::User(
+                        value,
+                        self.token(),
+                        Backtrace::default(),
+                    ))
+                }
+            },
+            CATCH => match self.pop() {
+                Value::Object(Object::Error(err)) => self.push(err.as_ref().clone().into()),
+                _ => unreachable!(),
+            },
+            RETHROW => match self.pop() {
+                Value::Object(Object::Error(err)) => return Err(err.as_ref().clone()),
+                _ => unreachable!(),
+            },
             ITER => {
                 let iterable: Iterable = self
-                    .last_typed(&[DataType::String, DataType::List])?
-                    .clone()
+                    .pop_typed(&[DataType::String, DataType::List, DataType::Iterator])?
                     .try_into()
                     .unwrap();
                 self.push(Value::from(iterable))
@@ -615,8 +1106,11 @@ impl<'a> Frame<'a> {
                     }
                     tmp
                 };
-                let popped = self.pop_typed(&[DataType::HashMap])?;
-                let hash_map: Rc<RefCell<HashMap<String, Value>>> = popped.try_into().unwrap();
+                let hash_map: Rc<RefCell<HashMap<String, Value>>> =
+                    match self.pop_typed(&[DataType::HashMap, DataType::Module])? {
+                        Value::Object(Object::Module(module)) => module.exports(),
+                        popped => popped.try_into().unwrap(),
+                    };
                 let hash_map = hash_map.borrow();
                 for (key, default) in keys {
                     let value = match hash_map.get(&key).cloned() {
@@ -644,59 +1138,111 @@ impl<'a> Frame<'a> {
             }
             UNKNOWN => unreachable!(),
         }
-        Ok((returned, advance))
+        Ok(advance)
     }
 
-    fn run(&mut self) -> Result<Option<Value>, RuntimeError> {
-        if cfg!(feature = "verbose") {
-            println!(
-                "[VM] {}'s chunk",
-                Value::Object(Object::Closure(Rc::clone(&self.closure)))
-            )
-        }
-
-        while let Some(instr) = self.closure.chunk().read(self.ip) {
+    /// Runs the frames until the first one (i.e., the script's) runs off the end of its chunk.
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            // None of the objects are borrowed between instructions.
+            if gc::should_collect() {
+                gc::collect();
+            }
+            let instr = match self.chunk().read(self.frame().ip) {
+                Some(instr) => instr,
+                None => {
+                    self.frames.pop();
+                    return Ok(());
+                }
+            };
             if cfg!(feature = "verbose") {
-                println!("{}", self.ip)
+                println!("{}", self.frame().ip)
             }
             let size = instr.size();
+            let depth = self.frames.len();
+            // Rethrown errors, and caught ones thrown with `ألقي`, were traced back to this frame when they got caught.
+            let traced = match instr.op_code() {
+                RETHROW => true,
+                THROW => matches!(
+                    self.last(),
+                    Value::Object(Object::Error(err)) if !err.backtrace().frames().is_empty()
+                ),
+                _ => false,
+            };
             match self.run_instr(instr) {
-                Ok((returned, advance)) => {
-                    match returned {
-                        Some(returned) => return Ok(Some(returned)),
-                        None => {}
-                    }
-                    if advance {
-                        self.ip += size;
+                // Pushing or popping frames takes care of the ips itself.
+                Ok(advance) => {
+                    if advance && self.frames.len() == depth {
+                        self.frame_mut().ip += size;
                     }
                 }
-                Err(mut err) => match self.handlers.pop() {
-                    Some(handler) => {
-                        self.state.close_upvalues(handler.slots());
-                        self.state.locals.drain(handler.slots()..);
-                        self.push(err.into());
-                        self.ip = handler.ip();
-                    }
-                    None => {
-                        err.backtrace_mut().push(self.closure.name(), self.token());
+                Err(err) => self.unwind(err, traced)?,
+            }
+        }
+    }
+
+    /// Gives `err` to the closest handler popping the frames without any, fails when none of the frames has one.
+    fn unwind(&mut self, mut err: RuntimeError, mut traced: bool) -> Result<(), RuntimeError> {
+        loop {
+            if !traced {
+                err.backtrace_mut()
+                    .push(self.frame().closure.name(), self.token());
+            }
+            traced = false;
+            match self.frame_mut().handlers.pop() {
+                Some(handler) => {
+                    self.close_upvalues(handler.slots());
+                    self.locals.drain(handler.slots()..);
+                    self.tmps.truncate(handler.tmps());
+                    self.push(Value::Object(Object::Error(Rc::new(err))));
+                    self.frame_mut().ip = handler.ip();
+                    return Ok(());
+                }
+                None => {
+                    self.frames.pop();
+                    if self.frames.is_empty() {
                         return Err(err);
                     }
-                },
+                }
             }
         }
-        Ok(None)
     }
 }
 
+/// What the caller of a frame gets when it returns.
+enum Return {
+    /// The returned value replaces the callee and its arguments.
+    Value,
+    /// The instance replaces the constructor and its arguments instead of the returned value.
+    Instance(Value),
+    /// Same as `Value` but the returned module is also kept by its path, for the later imports of it.
+    Module(String),
+    /// Nothing is given when the generator finishes, and the yielded value is given otherwise.
+    Generator(Rc<RefCell<Generator>>),
+}
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    slots: usize,
+    /// The size of tmps before the callee and its arguments, or before the generator's tmps when it is resumed.
+    tmps: usize,
+    argc: usize,
+    handlers: Vec<Handler>,
+    ret: Return,
+}
+
 #[derive(Debug, Clone)]
 struct Handler {
     ip: usize,
     slots: usize,
+    /// The size of tmps when the handler was appended.
+    tmps: usize,
 }
 
 impl Handler {
-    fn new(ip: usize, slots: usize) -> Self {
-        Self { ip, slots }
+    fn new(ip: usize, slots: usize, tmps: usize) -> Self {
+        Self { ip, slots, tmps }
     }
 
     fn ip(&self) -> usize {
@@ -706,4 +1252,8 @@ impl Handler {
     fn slots(&self) -> usize {
         self.slots
     }
+
+    fn tmps(&self) -> usize {
+        self.tmps
+    }
 }
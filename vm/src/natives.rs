@@ -0,0 +1,103 @@
+use compiler::chunk::gc;
+use compiler::chunk::value::{Arity, ArityType, DataType, Module, Native, Value};
+use compiler::error::{Backtrace, RuntimeError};
+use lexer::token::Token;
+use std::collections::HashMap;
+use std::{cell::RefCell, f64::consts, rc::Rc};
+
+/// Builds the module that is imported with `@name`, `name` must be one of `compiler::NATIVE_MODULES`.
+pub fn module(name: &str) -> Module {
+    let exports = match name {
+        "رياضيات" => math(),
+        "ذاكرة" => memory(),
+        _ => unreachable!(),
+    };
+    // Modules implemented by the VM can't be changed by the scripts importing them.
+    let constants = exports.keys().cloned().collect();
+    Module::new(Rc::new(RefCell::new(exports)), constants)
+}
+
+fn number(value: &Value, token: &Rc<Token>) -> Result<f64, RuntimeError> {
+    match value {
+        Value::Number(number) => Ok(*number),
+        value => Err(RuntimeError::Type(
+            vec![DataType::Number],
+            vec![value.typ()],
+            Rc::clone(token),
+            Backtrace::default(),
+        )),
+    }
+}
+
+/// Reduces the numbers passed to the native (i.e., the arguments after the first) with `f`.
+fn fold(args: &[Value], token: &Rc<Token>, f: fn(f64, f64) -> f64) -> Result<Value, RuntimeError> {
+    let mut acc = number(&args[1], token)?;
+    for arg in &args[2..] {
+        acc = f(acc, number(arg, token)?)
+    }
+    Ok(Value::from(acc))
+}
+
+fn math() -> HashMap<String, Value> {
+    macro_rules! unary {
+        ($f:expr) => {
+            Value::from(Native::new(
+                |args, token| Ok(Value::from($f(number(&args[1], &token)?))),
+                Arity::new(ArityType::Fixed, 1, 0),
+            ))
+        };
+    }
+
+    HashMap::from([
+        ("ط".to_owned(), Value::from(consts::PI)),
+        ("ه".to_owned(), Value::from(consts::E)),
+        ("جذر".to_owned(), unary!(f64::sqrt)),
+        ("مطلق".to_owned(), unary!(f64::abs)),
+        ("أرضية".to_owned(), unary!(f64::floor)),
+        ("سقف".to_owned(), unary!(f64::ceil)),
+        ("تقريب".to_owned(), unary!(f64::round)),
+        ("جيب".to_owned(), unary!(f64::sin)),
+        ("جتا".to_owned(), unary!(f64::cos)),
+        (
+            "أكبر".to_owned(),
+            Value::from(Native::new(
+                |args, token| fold(&args, &token, f64::max),
+                Arity::new(ArityType::Variadic, 1, 0),
+            )),
+        ),
+        (
+            "أصغر".to_owned(),
+            Value::from(Native::new(
+                |args, token| fold(&args, &token, f64::min),
+                Arity::new(ArityType::Variadic, 1, 0),
+            )),
+        ),
+    ])
+}
+
+/// Exposes the cycle collector, which also runs by itself as objects get created.
+fn memory() -> HashMap<String, Value> {
+    HashMap::from([
+        (
+            "اجمع".to_owned(),
+            Value::from(Native::new(
+                |_, _| Ok(Value::from(gc::collect())),
+                Arity::default(),
+            )),
+        ),
+        (
+            "إحصائيات".to_owned(),
+            Value::from(Native::new(
+                |_, _| {
+                    let stats = gc::stats();
+                    Ok(Value::from(HashMap::from([
+                        ("المتتبعة".to_owned(), Value::from(stats.tracked)),
+                        ("مرات_الجمع".to_owned(), Value::from(stats.collections)),
+                        ("المحررة".to_owned(), Value::from(stats.freed)),
+                    ])))
+                },
+                Arity::default(),
+            )),
+        ),
+    ])
+}
@@ -0,0 +1,48 @@
+use compiler::chunk::gc;
+use compiler::error::RuntimeError;
+use compiler::{Compiler, CompilerType};
+use lexer::Lexer;
+use parser::Parser;
+use std::rc::Rc;
+use vm::Vm;
+
+fn run(vm: &mut Vm, source: &str) -> Result<(), RuntimeError> {
+    let tokens = Lexer::new(source.to_owned(), None).lex();
+    let token = Rc::clone(tokens.last().unwrap());
+    let ast = Parser::new(tokens).parse().unwrap();
+    let chunk = Compiler::new(CompilerType::Script, &ast, token)
+        .compile()
+        .unwrap();
+    vm.run(chunk)
+}
+
+#[test]
+fn uncaught_errors_leave_nothing_behind() {
+    let mut vm = Vm::new();
+    let failed = run(
+        &mut vm,
+        "
+متغير ع = عدم
+دالة ف(أ) {
+  متغير ق = [أ]
+  ع = || { أرجع ق }
+  ألقي \"خطأ\"
+}
+ف(1)
+",
+    );
+    assert!(failed.is_err());
+    // The closure keeps its captured list after its frame is gone.
+    run(
+        &mut vm,
+        "
+إن (ع()[0] != 1) {
+  ألقي \"خطأ\"
+}
+ع = عدم
+",
+    )
+    .unwrap();
+    gc::collect();
+    assert_eq!(gc::stats().tracked, 0);
+}